@@ -0,0 +1,110 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+thread_local! {
+    /// Bytes allocated minus bytes freed by the current thread since its last reset.
+    static THREAD_CURRENT: Cell<isize> = const { Cell::new(0) };
+    /// Peak of [`THREAD_CURRENT`] since its last reset.
+    static THREAD_PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+/// Global allocator wrapping [`System`] that tracks current and peak heap usage,
+/// both for the whole process and per thread.
+#[derive(Debug, Default)]
+pub struct TrackingAllocator {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl TrackingAllocator {
+    /// Create a new tracking allocator.
+    pub const fn new() -> Self {
+        Self {
+            current: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// Get the heap usage in bytes.
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Get the peak heap usage in bytes since the last reset.
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Reset the peak heap usage to the current usage.
+    pub fn reset_peak(&self) {
+        self.peak.store(self.current(), Ordering::Relaxed);
+    }
+
+    /// Get the peak heap usage in bytes of the current thread since its last reset,
+    /// counting only memory allocated and freed on this thread.
+    ///
+    /// Unlike [`TrackingAllocator::peak`], this is not affected by other threads.
+    pub fn thread_peak(&self) -> usize {
+        THREAD_PEAK.try_with(Cell::get).unwrap_or_default().max(0) as usize
+    }
+
+    /// Reset the heap usage tracking of the current thread.
+    pub fn reset_thread_peak(&self) {
+        let _ = THREAD_CURRENT.try_with(|current| current.set(0));
+        let _ = THREAD_PEAK.try_with(|peak| peak.set(0));
+    }
+
+    fn grow(&self, size: usize) {
+        let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+        // thread locals may be unavailable while the thread is torn down
+        let _ = THREAD_CURRENT.try_with(|current| {
+            let value = current.get() + size as isize;
+            current.set(value);
+            let _ = THREAD_PEAK.try_with(|peak| peak.set(peak.get().max(value)));
+        });
+    }
+
+    fn shrink(&self, size: usize) {
+        self.current.fetch_sub(size, Ordering::Relaxed);
+        let _ = THREAD_CURRENT.try_with(|current| current.set(current.get() - size as isize));
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            self.grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.shrink(layout.size());
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            self.grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                self.grow(new_size - layout.size());
+            } else {
+                self.shrink(layout.size() - new_size);
+            }
+        }
+        new_ptr
+    }
+}
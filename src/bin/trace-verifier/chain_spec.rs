@@ -0,0 +1,102 @@
+use serde::Deserialize;
+use stateless_block_verifier::HardforkConfig;
+use std::{path::Path, str::FromStr};
+
+/// Geth-style genesis file, only the fields relevant to verification are parsed.
+#[derive(Debug, Deserialize)]
+struct Genesis {
+    config: ChainConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChainConfig {
+    chain_id: u64,
+    curie_block: Option<u64>,
+}
+
+/// Chain id and hardfork configuration loaded from a genesis file.
+#[derive(Debug, Clone)]
+pub struct ChainSpec {
+    pub chain_id: u64,
+    pub fork_config: HardforkConfig,
+}
+
+impl ChainSpec {
+    /// Load the chain spec from a geth-style genesis JSON file.
+    pub fn from_genesis_file(path: &Path) -> anyhow::Result<Self> {
+        let genesis: Genesis = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let mut fork_config = HardforkConfig::default();
+        // like geth, a fork without activation block is never activated
+        fork_config.set_curie_block(
+            genesis
+                .config
+                .curie_block
+                .unwrap_or(HardforkConfig::NOT_SCHEDULED),
+        );
+        Ok(Self {
+            chain_id: genesis.config.chain_id,
+            fork_config,
+        })
+    }
+}
+
+/// Hardfork activation override, parsed from `NAME=BLOCK`.
+#[derive(Debug, Copy, Clone)]
+pub struct ForkOverride {
+    fork: Fork,
+    block: u64,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Fork {
+    Curie,
+}
+
+impl ForkOverride {
+    /// Apply the override to a hardfork configuration.
+    pub fn apply(&self, config: &mut HardforkConfig) {
+        match self.fork {
+            Fork::Curie => config.set_curie_block(self.block),
+        };
+    }
+}
+
+impl FromStr for ForkOverride {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, block) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expected NAME=BLOCK, got {s:?}"))?;
+        let fork = match name.to_ascii_lowercase().as_str() {
+            "curie" => Fork::Curie,
+            _ => anyhow::bail!("unknown hardfork {name:?}"),
+        };
+        Ok(Self {
+            fork,
+            block: block.parse()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::primitives::SpecId;
+
+    #[test]
+    fn test_parse_fork_override() {
+        let fork_override = "Curie=100".parse::<ForkOverride>().unwrap();
+        let mut config = HardforkConfig::default();
+        fork_override.apply(&mut config);
+        assert_eq!(config.get_spec_id(99), SpecId::BERNOULLI);
+        assert_eq!(config.get_spec_id(100), SpecId::CURIE);
+
+        let e = "curie".parse::<ForkOverride>().unwrap_err();
+        assert_eq!(e.to_string(), "expected NAME=BLOCK, got \"curie\"");
+        let e = "bernoulli=100".parse::<ForkOverride>().unwrap_err();
+        assert_eq!(e.to_string(), "unknown hardfork \"bernoulli\"");
+        assert!("curie=latest".parse::<ForkOverride>().is_err());
+    }
+}
@@ -1,8 +1,14 @@
+use crate::utils::VerifyOptions;
 use clap::Subcommand;
 use stateless_block_verifier::HardforkConfig;
 
-mod run_file;
-mod run_rpc;
+mod bench;
+mod bisect;
+mod chain_info;
+mod completions;
+pub mod run_file;
+pub mod run_rpc;
+mod run_watch;
 
 #[derive(Subcommand)]
 pub enum Commands {
@@ -12,17 +18,37 @@ pub enum Commands {
     /// Fetch and verify traces from geth rpc
     #[command(name = "run-rpc")]
     RunRpc(run_rpc::RunRpcCommand),
+    /// Verify trace files as they appear in a directory
+    #[command(name = "run-watch")]
+    RunWatch(run_watch::RunWatchCommand),
+    /// Print the resolved hardforks of a chain at a block
+    #[command(name = "chain-info")]
+    ChainInfo(chain_info::ChainInfoCommand),
+    /// Repeatedly verify a trace file and report timing statistics
+    #[command(name = "bench")]
+    Bench(bench::BenchCommand),
+    /// Find the first transaction of a trace file whose post state diverges from the trace
+    #[command(name = "bisect")]
+    Bisect(bisect::BisectCommand),
+    /// Print a shell completion script
+    #[command(name = "completions")]
+    Completions(completions::CompletionsCommand),
 }
 
 impl Commands {
     pub async fn run(
         self,
         fork_config: impl Fn(u64) -> HardforkConfig,
-        disable_checks: bool,
+        options: VerifyOptions,
     ) -> anyhow::Result<()> {
         match self {
-            Commands::RunFile(cmd) => cmd.run(fork_config, disable_checks).await,
-            Commands::RunRpc(cmd) => cmd.run(fork_config, disable_checks).await,
+            Commands::RunFile(cmd) => cmd.run(fork_config, options).await,
+            Commands::RunRpc(cmd) => cmd.run(fork_config, options).await,
+            Commands::RunWatch(cmd) => cmd.run(fork_config, options).await,
+            Commands::ChainInfo(cmd) => cmd.run(fork_config).await,
+            Commands::Bench(cmd) => cmd.run(fork_config, options).await,
+            Commands::Bisect(cmd) => cmd.run(fork_config, options).await,
+            Commands::Completions(cmd) => cmd.run().await,
         }
     }
 }
@@ -0,0 +1,171 @@
+use crate::utils::{self, VerifyOptions};
+use clap::Args;
+use eth_types::ToWord;
+use serde::{Deserialize, Serialize};
+use stateless_block_verifier::{EvmExecutor, HardforkConfig};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Args)]
+pub struct BenchCommand {
+    /// Path to the trace file
+    #[arg(short, long, default_value = "trace.json")]
+    path: PathBuf,
+    /// Number of verification runs
+    #[arg(short = 'n', long, default_value = "10")]
+    iterations: usize,
+    /// Write the benchmark result as JSON to this file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Compare against a result previously written with `--output`
+    #[arg(long)]
+    baseline: Option<PathBuf>,
+}
+
+/// Timing statistics of a benchmark run.
+#[derive(Debug, Serialize, Deserialize)]
+struct BenchResult {
+    block_number: u64,
+    iterations: usize,
+    mean_ms: f64,
+    p50_ms: f64,
+    p95_ms: f64,
+    gas_per_sec: f64,
+    peak_heap: Option<usize>,
+}
+
+impl BenchCommand {
+    pub async fn run(
+        self,
+        fork_config: impl Fn(u64) -> HardforkConfig,
+        options: VerifyOptions,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(self.iterations > 0, "iterations must be positive");
+
+        let l2_trace = utils::read_trace(&self.path).await?;
+        let fork_config = fork_config(l2_trace.chain_id);
+        let iterations = self.iterations;
+        let result = tokio::task::spawn_blocking(move || {
+            bench(&l2_trace, &fork_config, &options, iterations)
+        })
+        .await??;
+
+        println!("block: {}", result.block_number);
+        println!("iterations: {}", result.iterations);
+        println!("mean: {:.3} ms", result.mean_ms);
+        println!("p50: {:.3} ms", result.p50_ms);
+        println!("p95: {:.3} ms", result.p95_ms);
+        println!("gas/s: {:.0}", result.gas_per_sec);
+        if let Some(peak_heap) = result.peak_heap {
+            println!("peak heap: {} bytes", peak_heap);
+        }
+
+        if let Some(baseline) = self.baseline.as_deref() {
+            let baseline: BenchResult = serde_json::from_slice(&tokio::fs::read(baseline).await?)?;
+            println!("compared to baseline:");
+            println!("  mean: {}", change(baseline.mean_ms, result.mean_ms));
+            println!("  p50: {}", change(baseline.p50_ms, result.p50_ms));
+            println!("  p95: {}", change(baseline.p95_ms, result.p95_ms));
+            println!(
+                "  gas/s: {}",
+                change(baseline.gas_per_sec, result.gas_per_sec)
+            );
+        }
+
+        if let Some(output) = self.output.as_deref() {
+            tokio::fs::write(output, serde_json::to_vec_pretty(&result)?).await?;
+        }
+        Ok(())
+    }
+}
+
+fn bench(
+    l2_trace: &eth_types::l2_types::BlockTrace,
+    fork_config: &HardforkConfig,
+    options: &VerifyOptions,
+    iterations: usize,
+) -> anyhow::Result<BenchResult> {
+    let root_after = l2_trace.storage_trace.root_after.to_word();
+    let mut durations = Vec::with_capacity(iterations);
+    #[cfg(feature = "track-memory")]
+    let mut peak_heap = None;
+    #[cfg(not(feature = "track-memory"))]
+    let peak_heap = None;
+
+    for _ in 0..iterations {
+        #[cfg(feature = "track-memory")]
+        crate::ALLOCATOR.reset_thread_peak();
+
+        let now = Instant::now();
+        let mut executor = EvmExecutor::new_with_limits(
+            l2_trace,
+            fork_config,
+            options.disable_checks,
+            &options.limits,
+        )?;
+        if let Some(memory_limit) = options.memory_limit {
+            executor.set_memory_limit(memory_limit);
+        }
+        let revm_root_after = executor.try_handle_block(l2_trace)?.to_word();
+        durations.push(now.elapsed());
+
+        anyhow::ensure!(revm_root_after == root_after, "root mismatch");
+
+        #[cfg(feature = "track-memory")]
+        {
+            peak_heap = peak_heap.max(Some(crate::ALLOCATOR.thread_peak()));
+        }
+    }
+
+    durations.sort();
+    let mean = durations.iter().sum::<Duration>() / iterations as u32;
+    let gas_used = l2_trace.header.gas_used.as_u64();
+
+    Ok(BenchResult {
+        block_number: l2_trace.header.number.unwrap().as_u64(),
+        iterations,
+        mean_ms: as_ms(mean),
+        p50_ms: as_ms(percentile(&durations, 50)),
+        p95_ms: as_ms(percentile(&durations, 95)),
+        gas_per_sec: gas_used as f64 / mean.as_secs_f64(),
+        peak_heap,
+    })
+}
+
+/// Nearest-rank percentile of sorted durations.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn change(baseline: f64, current: f64) -> String {
+    format!(
+        "{:.3} -> {:.3} ({:+.2}%)",
+        baseline,
+        current,
+        (current - baseline) / baseline * 100.0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let single = [Duration::from_millis(7)];
+        assert_eq!(percentile(&single, 50), single[0]);
+        assert_eq!(percentile(&single, 99), single[0]);
+
+        let sorted: Vec<_> = (1..=20).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 0), Duration::from_millis(1));
+        assert_eq!(percentile(&sorted, 50), Duration::from_millis(10));
+        assert_eq!(percentile(&sorted, 95), Duration::from_millis(19));
+        assert_eq!(percentile(&sorted, 99), Duration::from_millis(20));
+        assert_eq!(percentile(&sorted, 100), Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,275 @@
+use crate::utils::{self, VerifyOptions};
+use clap::Args;
+use eth_types::{
+    l2_types::{BlockTrace, ExecutionResult},
+    ToWord, H160, H256,
+};
+use revm::{
+    primitives::{keccak256, AccountInfo, Address, B256, U256},
+    DatabaseRef,
+};
+use serde::Deserialize;
+use stateless_block_verifier::{EvmExecutor, HardforkConfig};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
+
+#[derive(Args)]
+pub struct BisectCommand {
+    /// Path to the trace file
+    #[arg(short, long, default_value = "trace.json")]
+    path: PathBuf,
+}
+
+/// State of an account before a transaction, as recorded by the prestate tracer.
+#[derive(Debug, Deserialize)]
+struct PrestateAccount {
+    #[serde(default)]
+    balance: eth_types::U256,
+    #[serde(default)]
+    nonce: u64,
+    #[serde(default)]
+    code: eth_types::Bytes,
+    #[serde(default)]
+    storage: HashMap<H256, H256>,
+}
+
+/// Account state after a transaction that differs from the trace.
+#[derive(Debug)]
+enum Mismatch {
+    Balance {
+        address: Address,
+        local: U256,
+        expected: U256,
+    },
+    Nonce {
+        address: Address,
+        local: u64,
+        expected: u64,
+    },
+    Code {
+        address: Address,
+    },
+    Storage {
+        address: Address,
+        index: U256,
+        local: U256,
+        expected: U256,
+    },
+}
+
+impl BisectCommand {
+    pub async fn run(
+        self,
+        fork_config: impl Fn(u64) -> HardforkConfig,
+        options: VerifyOptions,
+    ) -> anyhow::Result<()> {
+        let l2_trace = utils::read_trace(&self.path).await?;
+        let prestates = read_prestates(&self.path).await?;
+        let fork_config = fork_config(l2_trace.chain_id);
+        let (divergence, root_matches) = tokio::task::spawn_blocking(move || {
+            bisect(&l2_trace, &fork_config, &options, &prestates)
+        })
+        .await??;
+
+        match divergence {
+            Some((idx, mismatches)) => {
+                println!("first divergent tx: #{idx}");
+                for mismatch in mismatches {
+                    println!("  {mismatch}");
+                }
+            }
+            None if root_matches => println!("state root matches, no divergent tx"),
+            None => println!(
+                "state root mismatch, but every tx matches the account and storage states in the trace"
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Execute a block, comparing the state after each transaction with the state after it in
+/// the trace: the accounts after the transaction and the accounts and storage slots read by
+/// the next one. Stops at the first transaction that diverges.
+///
+/// Returns the divergent transaction with its mismatches, and whether the state root matches.
+fn bisect(
+    l2_trace: &BlockTrace,
+    fork_config: &HardforkConfig,
+    options: &VerifyOptions,
+    prestates: &[HashMap<H160, PrestateAccount>],
+) -> anyhow::Result<(Option<(usize, Vec<Mismatch>)>, bool)> {
+    let mut executor = EvmExecutor::new_with_limits(l2_trace, fork_config, true, &options.limits)?;
+    if let Some(memory_limit) = options.memory_limit {
+        executor.set_memory_limit(memory_limit);
+    }
+
+    let mut divergence = None;
+    let root_after = executor.try_handle_block_with(l2_trace, |idx, db| {
+        let mut mismatches = Vec::new();
+        if let Some(exec) = l2_trace.execution_results.get(idx) {
+            check_accounts_after(exec, db, &mut mismatches);
+        }
+        if let Some(prestate) = prestates.get(idx + 1) {
+            check_prestate(prestate, db, &mut mismatches);
+        }
+        if mismatches.is_empty() {
+            return ControlFlow::Continue(());
+        }
+        divergence = Some((idx, mismatches));
+        ControlFlow::Break(())
+    })?;
+
+    let root_matches = root_after.to_word() == l2_trace.storage_trace.root_after.to_word();
+    Ok((divergence, root_matches))
+}
+
+fn check_accounts_after(
+    exec: &ExecutionResult,
+    db: &impl DatabaseRef<Error = Infallible>,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    for account in exec.account_after.iter() {
+        let address = Address::from(account.address.0);
+        let local = basic(db, address);
+        let code_matches = if account.keccak_code_hash.is_zero() {
+            local.is_empty()
+        } else {
+            local.keccak_code_hash == B256::from(account.keccak_code_hash.0)
+        };
+        check_account(
+            address,
+            &local,
+            U256::from_limbs(account.balance.0),
+            account.nonce,
+            code_matches,
+            mismatches,
+        );
+    }
+}
+
+fn check_prestate(
+    prestate: &HashMap<H160, PrestateAccount>,
+    db: &impl DatabaseRef<Error = Infallible>,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    for (address, account) in prestate.iter() {
+        let address = Address::from(address.0);
+        let local = basic(db, address);
+        check_account(
+            address,
+            &local,
+            U256::from_limbs(account.balance.0),
+            account.nonce,
+            local.keccak_code_hash == keccak256(&account.code),
+            mismatches,
+        );
+        for (index, value) in account.storage.iter() {
+            let index = U256::from_be_bytes(index.0);
+            let expected = U256::from_be_bytes(value.0);
+            let local = db.storage_ref(address, index).unwrap();
+            if local != expected {
+                mismatches.push(Mismatch::Storage {
+                    address,
+                    index,
+                    local,
+                    expected,
+                });
+            }
+        }
+    }
+}
+
+fn check_account(
+    address: Address,
+    local: &AccountInfo,
+    balance: U256,
+    nonce: u64,
+    code_matches: bool,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    if local.balance != balance {
+        mismatches.push(Mismatch::Balance {
+            address,
+            local: local.balance,
+            expected: balance,
+        });
+    }
+    if local.nonce != nonce {
+        mismatches.push(Mismatch::Nonce {
+            address,
+            local: local.nonce,
+            expected: nonce,
+        });
+    }
+    if !code_matches {
+        mismatches.push(Mismatch::Code { address });
+    }
+}
+
+fn basic(db: &impl DatabaseRef<Error = Infallible>, address: Address) -> AccountInfo {
+    db.basic_ref(address).unwrap().unwrap_or_default()
+}
+
+/// Read the prestate of each transaction from a trace file,
+/// either a bare trace or a JSON-RPC response.
+async fn read_prestates(path: &Path) -> anyhow::Result<Vec<HashMap<H160, PrestateAccount>>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Trace {
+        execution_results: Vec<TxPrestate>,
+    }
+    #[derive(Deserialize)]
+    struct TxPrestate {
+        #[serde(default)]
+        prestate: HashMap<H160, PrestateAccount>,
+    }
+
+    let trace = tokio::fs::read_to_string(path).await?;
+    let trace = serde_json::from_str::<Trace>(&trace).or_else(|_| {
+        #[derive(Deserialize)]
+        struct TraceJsonRpcResult {
+            result: Trace,
+        }
+        serde_json::from_str::<TraceJsonRpcResult>(&trace).map(|rpc| rpc.result)
+    })?;
+    Ok(trace
+        .execution_results
+        .into_iter()
+        .map(|tx| tx.prestate)
+        .collect())
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Balance {
+                address,
+                local,
+                expected,
+            } => write!(
+                f,
+                "{address} balance: local {local:#x}, trace {expected:#x}"
+            ),
+            Mismatch::Nonce {
+                address,
+                local,
+                expected,
+            } => write!(f, "{address} nonce: local {local}, trace {expected}"),
+            Mismatch::Code { address } => write!(f, "{address} code hash differs"),
+            Mismatch::Storage {
+                address,
+                index,
+                local,
+                expected,
+            } => write!(
+                f,
+                "{address} storage {index:#x}: local {local:#x}, trace {expected:#x}"
+            ),
+        }
+    }
+}
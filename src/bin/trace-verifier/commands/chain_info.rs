@@ -0,0 +1,34 @@
+use clap::Args;
+use stateless_block_verifier::HardforkConfig;
+
+#[derive(Args)]
+pub struct ChainInfoCommand {
+    /// Chain id
+    #[arg(long)]
+    chain_id: u64,
+    /// Block number
+    #[arg(short, long)]
+    block: u64,
+}
+
+impl ChainInfoCommand {
+    pub async fn run(self, fork_config: impl Fn(u64) -> HardforkConfig) -> anyhow::Result<()> {
+        let fork_config = fork_config(self.chain_id);
+        println!("chain id: {}", self.chain_id);
+        println!("block: {}", self.block);
+        println!("spec id: {:?}", fork_config.get_spec_id(self.block));
+        println!(
+            "active forks: {:?}",
+            fork_config.active_hardforks_at(self.block)
+        );
+        println!("active precompiles:");
+        for precompile in fork_config.active_precompiles_at(self.block) {
+            println!("  {}", precompile.0);
+        }
+        match fork_config.next_fork_after(self.block) {
+            Some((spec_id, height)) => println!("next fork: {spec_id:?} at block {height}"),
+            None => println!("next fork: none"),
+        }
+        Ok(())
+    }
+}
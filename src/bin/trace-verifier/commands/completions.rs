@@ -0,0 +1,18 @@
+use clap::{Args, CommandFactory};
+use clap_complete::Shell;
+
+#[derive(Args)]
+pub struct CompletionsCommand {
+    /// Shell to generate the completion script for
+    #[arg(value_enum)]
+    shell: Shell,
+}
+
+impl CompletionsCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let mut cmd = crate::Cli::command();
+        let name = cmd.get_name().to_string();
+        clap_complete::generate(self.shell, &mut cmd, name, &mut std::io::stdout());
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
-use crate::utils;
+use crate::{
+    config::RunFileConfig,
+    report::ReportWriter,
+    utils::{self, VerifyOptions},
+};
 use clap::Args;
-use eth_types::l2_types::BlockTrace;
+use futures::StreamExt;
 use stateless_block_verifier::HardforkConfig;
 use std::path::PathBuf;
 
@@ -9,31 +13,60 @@ pub struct RunFileCommand {
     /// Path to the trace file
     #[arg(short, long, default_value = "trace.json")]
     path: Vec<PathBuf>,
+    /// Write per-block results to this file, as CSV if it ends with `.csv`, JSON lines otherwise
+    #[arg(long)]
+    report: Option<PathBuf>,
+    /// Number of trace files verified in parallel [default: 1]
+    #[arg(short, long)]
+    jobs: Option<usize>,
 }
 
 impl RunFileCommand {
+    /// Fill in options not given on the command line from the config file.
+    pub fn merge_config(&mut self, config: &RunFileConfig) {
+        if self.report.is_none() {
+            self.report = config.report.clone();
+        }
+        self.jobs = self.jobs.or(config.jobs);
+    }
+
     pub async fn run(
         self,
         fork_config: impl Fn(u64) -> HardforkConfig,
-        disable_checks: bool,
+        options: VerifyOptions,
     ) -> anyhow::Result<()> {
-        for path in self.path {
-            info!("Reading trace from {:?}", path);
-            let trace = tokio::fs::read_to_string(&path).await?;
-            let l2_trace: BlockTrace = serde_json::from_str(&trace).unwrap_or_else(|_| {
-                #[derive(serde::Deserialize, Default, Debug, Clone)]
-                pub struct BlockTraceJsonRpcResult {
-                    pub result: BlockTrace,
+        let mut report_writer = self
+            .report
+            .as_deref()
+            .map(ReportWriter::create)
+            .transpose()?;
+
+        let fork_config = &fork_config;
+        let options = &options;
+        let mut reports = futures::stream::iter(self.path)
+            .map(|path| async move {
+                // a bad trace is reported as failed, the other files are still verified
+                let report = utils::verify_file(&path, fork_config, options).await;
+                if !report.success {
+                    error!("Verification failed for {:?}", path);
                 }
-                serde_json::from_str::<BlockTraceJsonRpcResult>(&trace)
-                    .unwrap()
-                    .result
-            });
-            let fork_config = fork_config(l2_trace.chain_id);
-            tokio::task::spawn_blocking(move || {
-                utils::verify(l2_trace, &fork_config, disable_checks, false)
+                report
             })
-            .await?;
+            .buffer_unordered(self.jobs.unwrap_or(1).max(1));
+
+        let mut failed = 0;
+        while let Some(report) = reports.next().await {
+            if let Some(report_writer) = report_writer.as_mut() {
+                report_writer.write(&report)?;
+            }
+            if !report.success {
+                failed += 1;
+            }
+        }
+
+        if failed > 0 {
+            error!("{failed} trace(s) failed verification");
+            std::process::exit(1);
         }
         Ok(())
     }
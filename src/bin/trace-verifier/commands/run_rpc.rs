@@ -1,30 +1,47 @@
-use crate::utils;
+use crate::{
+    config::RunRpcConfig,
+    failover::FailoverProvider,
+    report::ReportWriter,
+    transport::Transport,
+    utils::{self, VerifyOptions},
+};
 use clap::Args;
 use eth_types::l2_types::BlockTrace;
-use ethers_providers::{Http, Middleware, Provider};
+use ethers_providers::{
+    JsonRpcClient, Middleware, Provider, Quorum, QuorumProvider, WeightedProvider,
+};
 use futures::future::OptionFuture;
 use stateless_block_verifier::HardforkConfig;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 use url::Url;
 
+const DEFAULT_URL: &str = "http://localhost:8545";
+
 #[derive(Args)]
 pub struct RunRpcCommand {
-    /// RPC URL
-    #[arg(short, long, default_value = "http://localhost:8545")]
-    url: Url,
+    /// RPC URL (http or ws), can be repeated to fail over to the next one on transport errors
+    /// [default: http://localhost:8545]
+    #[arg(short, long)]
+    url: Vec<Url>,
+    /// Path to the node IPC socket, tried before the RPC URLs
+    #[arg(long)]
+    ipc: Option<PathBuf>,
+    /// Query all RPC URLs and require this many of them to return the same response
+    #[arg(short, long)]
+    quorum: Option<usize>,
     /// Start Block number
     #[arg(short, long, default_value = "latest")]
     start_block: StartBlockSpec,
     /// End block number
     #[arg(short, long)]
     end_block: Option<u64>,
-    /// parallel worker count
-    #[arg(short = 'j', long, default_value = "1")]
-    parallel: usize,
+    /// parallel worker count [default: 1]
+    #[arg(short = 'j', long)]
+    parallel: Option<usize>,
     /// Do not exit on verification failure, log the error and continue
     #[arg(short, long)]
     log_error: Option<PathBuf>,
@@ -36,6 +53,12 @@ pub struct RunRpcCommand {
         conflicts_with = "end_block"
     )]
     block_list: Option<PathBuf>,
+    /// Write per-block results to this file, as CSV if it ends with `.csv`, JSON lines otherwise
+    #[arg(long)]
+    report: Option<PathBuf>,
+    /// Directory to cache fetched traces in, cached traces are not fetched again
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -45,14 +68,73 @@ pub enum StartBlockSpec {
 }
 
 impl RunRpcCommand {
+    /// Fill in options not given on the command line from the config file.
+    pub fn merge_config(&mut self, config: &RunRpcConfig) {
+        if self.url.is_empty() {
+            self.url = config.url.clone();
+        }
+        if self.ipc.is_none() {
+            self.ipc = config.ipc.clone();
+        }
+        self.quorum = self.quorum.or(config.quorum);
+        self.parallel = self.parallel.or(config.parallel);
+        if self.report.is_none() {
+            self.report = config.report.clone();
+        }
+        if self.cache_dir.is_none() {
+            self.cache_dir = config.cache_dir.clone();
+        }
+    }
+
     pub async fn run(
-        self,
+        mut self,
         fork_config: impl Fn(u64) -> HardforkConfig,
-        disable_checks: bool,
+        options: VerifyOptions,
     ) -> anyhow::Result<()> {
-        info!("Running RPC command with url: {}", self.url);
-        let provider = Provider::new(Http::new(self.url));
+        if self.url.is_empty() && self.ipc.is_none() {
+            self.url.push(Url::parse(DEFAULT_URL)?);
+        }
+        let urls = self
+            .url
+            .iter()
+            .map(|url| url.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        info!("Running RPC command with url: {}", urls);
+        let mut clients = Vec::with_capacity(self.url.len() + 1);
+        if let Some(ipc) = self.ipc.as_deref() {
+            info!("Connecting to IPC socket {:?}", ipc);
+            clients.push(Transport::connect_ipc(ipc).await?);
+        }
+        for url in self.url.iter() {
+            clients.push(Transport::connect(url).await?);
+        }
 
+        if let Some(quorum) = self.quorum {
+            anyhow::ensure!(
+                quorum > 0 && quorum <= clients.len(),
+                "quorum must be between 1 and the number of RPC endpoints ({}), got {quorum}",
+                clients.len()
+            );
+            let quorum = QuorumProvider::new(
+                Quorum::ProviderCount(quorum),
+                clients.into_iter().map(WeightedProvider::new),
+            );
+            self.run_with_provider(Provider::new(quorum), fork_config, options)
+                .await
+        } else {
+            let failover = FailoverProvider::new(clients);
+            self.run_with_provider(Provider::new(failover), fork_config, options)
+                .await
+        }
+    }
+
+    async fn run_with_provider<P: JsonRpcClient + Clone + 'static>(
+        self,
+        provider: Provider<P>,
+        fork_config: impl Fn(u64) -> HardforkConfig,
+        options: VerifyOptions,
+    ) -> anyhow::Result<()> {
         let chain_id = provider.get_chainid().await?.as_u64();
         let fork_config = fork_config(chain_id);
 
@@ -63,7 +145,16 @@ impl RunRpcCommand {
 
         let mut current_block = start_block;
 
-        let (tx, rx) = async_channel::bounded(self.parallel);
+        let cache_dir = self
+            .cache_dir
+            .as_ref()
+            .map(|dir| dir.join(chain_id.to_string()));
+        if let Some(cache_dir) = cache_dir.as_ref() {
+            tokio::fs::create_dir_all(cache_dir).await?;
+        }
+
+        let parallel = self.parallel.unwrap_or(1);
+        let (tx, rx) = async_channel::bounded(parallel);
 
         let error_log = OptionFuture::from(
             self.log_error
@@ -74,33 +165,49 @@ impl RunRpcCommand {
         .transpose()?
         .map(|f| Arc::new(Mutex::new(f)));
 
+        let report_writer = self
+            .report
+            .as_deref()
+            .map(ReportWriter::create)
+            .transpose()?
+            .map(|w| Arc::new(std::sync::Mutex::new(w)));
+
         let handles = {
-            let mut handles = Vec::with_capacity(self.parallel);
-            for idx in 0..self.parallel {
+            let mut handles = Vec::with_capacity(parallel);
+            for idx in 0..parallel {
                 let _provider = provider.clone();
                 let rx = rx.clone();
                 let is_log_error = error_log.is_some();
                 let error_log = error_log.clone();
+                let cache_dir = cache_dir.clone();
+                let report_writer = report_writer.clone();
+                let options = options.clone();
+                let fork_config = fork_config.clone();
                 let handle = tokio::spawn(async move {
                     while let Ok(block_number) = rx.recv().await {
-                        let l2_trace: BlockTrace = _provider
-                            .request(
-                                "scroll_getBlockTraceByNumberOrHash",
-                                [format!("0x{:x}", block_number)],
-                            )
-                            .await?;
+                        let l2_trace =
+                            fetch_trace(&_provider, block_number, cache_dir.as_deref()).await?;
 
                         info!(
                             "worker#{idx}: load trace for block #{block_number}({:?})",
                             l2_trace.header.hash.unwrap()
                         );
 
-                        let success = tokio::task::spawn_blocking(move || {
-                            utils::verify(l2_trace, &fork_config, disable_checks, is_log_error)
+                        let options = options.clone();
+                        let fork_config = fork_config.clone();
+                        let report = tokio::task::spawn_blocking(move || {
+                            utils::verify(l2_trace, &fork_config, &options)
                         })
                         .await?;
 
-                        if !success {
+                        if let Some(report_writer) = report_writer.as_ref() {
+                            report_writer.lock().unwrap().write(&report)?;
+                        }
+
+                        if !report.success {
+                            if !is_log_error {
+                                std::process::exit(1);
+                            }
                             let mut guard = error_log.as_ref().unwrap().lock().await;
                             guard
                                 .write_all(format!("{block_number}\n").as_bytes())
@@ -156,6 +263,34 @@ impl RunRpcCommand {
     }
 }
 
+/// Fetch the trace of a block, through the cache directory if given.
+async fn fetch_trace<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    block_number: u64,
+    cache_dir: Option<&Path>,
+) -> anyhow::Result<BlockTrace> {
+    const METHOD: &str = "scroll_getBlockTraceByNumberOrHash";
+    let params = [format!("0x{:x}", block_number)];
+
+    let Some(cache_dir) = cache_dir else {
+        return Ok(provider.request(METHOD, params).await?);
+    };
+
+    let path = cache_dir.join(format!("{block_number}.json"));
+    if tokio::fs::try_exists(&path).await? {
+        debug!("load trace for block #{block_number} from cache {:?}", path);
+        let trace = tokio::fs::read(&path).await?;
+        return Ok(serde_json::from_slice(&trace)?);
+    }
+
+    let trace: serde_json::Value = provider.request(METHOD, params).await?;
+    // write to a temporary file first, so an interrupted write never leaves a broken cache entry
+    let tmp_path = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp_path, serde_json::to_vec(&trace)?).await?;
+    tokio::fs::rename(&tmp_path, &path).await?;
+    Ok(serde_json::from_value(trace)?)
+}
+
 impl FromStr for StartBlockSpec {
     type Err = <u64 as FromStr>::Err;
 
@@ -0,0 +1,90 @@
+use crate::{
+    report::ReportWriter,
+    utils::{self, VerifyOptions},
+};
+use clap::Args;
+use stateless_block_verifier::HardforkConfig;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Args)]
+pub struct RunWatchCommand {
+    /// Directory to watch for trace files, scanned periodically rather than through
+    /// filesystem notifications; files should be moved in atomically once completely written
+    #[arg(short, long)]
+    dir: PathBuf,
+    /// Seconds between scans of the directory
+    #[arg(long, default_value = "1")]
+    interval: u64,
+    /// Write per-block results to this file, as CSV if it ends with `.csv`, JSON lines otherwise
+    #[arg(long)]
+    report: Option<PathBuf>,
+}
+
+impl RunWatchCommand {
+    pub async fn run(
+        self,
+        fork_config: impl Fn(u64) -> HardforkConfig,
+        options: VerifyOptions,
+    ) -> anyhow::Result<()> {
+        let verified_dir = self.dir.join("verified");
+        let failed_dir = self.dir.join("failed");
+        tokio::fs::create_dir_all(&verified_dir).await?;
+        tokio::fs::create_dir_all(&failed_dir).await?;
+
+        let mut report_writer = self
+            .report
+            .as_deref()
+            .map(ReportWriter::create)
+            .transpose()?;
+
+        info!("Watching {:?} for trace files", self.dir);
+        let (mut verified, mut failed) = (0usize, 0usize);
+        let mut seen = HashSet::new();
+        loop {
+            for path in scan(&self.dir).await? {
+                if seen.contains(&path) {
+                    continue;
+                }
+                // keep watching if the trace is bad, it is moved out of the way below
+                let report = utils::verify_file(&path, &fork_config, &options).await;
+                if let Some(report_writer) = report_writer.as_mut() {
+                    report_writer.write(&report)?;
+                }
+                let success = report.success;
+
+                let dest_dir = if success {
+                    verified += 1;
+                    &verified_dir
+                } else {
+                    failed += 1;
+                    &failed_dir
+                };
+                if let Err(e) =
+                    tokio::fs::rename(&path, dest_dir.join(path.file_name().unwrap())).await
+                {
+                    // never verify the same file again, even if it can't be moved
+                    error!("Failed to move {:?} to {:?}: {e}", path, dest_dir);
+                    seen.insert(path);
+                }
+                info!("{verified} trace(s) verified, {failed} failed");
+            }
+            tokio::time::sleep(Duration::from_secs(self.interval)).await;
+        }
+    }
+}
+
+/// List the `.json` files directly in the directory, in name order.
+async fn scan(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if entry.file_type().await?.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
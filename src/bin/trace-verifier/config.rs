@@ -0,0 +1,59 @@
+use crate::{chain_spec::ForkOverride, LogFormat};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Options read from a TOML file given with `--config`,
+/// flags given on the command line take precedence.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub chain_spec: Option<PathBuf>,
+    pub curie_block: Option<u64>,
+    /// Hardfork overrides as `NAME=BLOCK`, applied before the ones on the command line
+    pub override_forks: Vec<String>,
+    pub disable_checks: bool,
+    pub memory_limit: Option<u64>,
+    pub tx_stats: bool,
+    pub check_reads: bool,
+    pub max_proof_nodes: Option<usize>,
+    pub max_code_bytes: Option<usize>,
+    pub max_txs: Option<usize>,
+    pub max_call_depth: Option<u64>,
+    pub access_list_dir: Option<PathBuf>,
+    pub log_format: Option<LogFormat>,
+    pub run_file: RunFileConfig,
+    pub run_rpc: RunRpcConfig,
+}
+
+/// Defaults for `run-file`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RunFileConfig {
+    pub report: Option<PathBuf>,
+    pub jobs: Option<usize>,
+}
+
+/// Defaults for `run-rpc`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RunRpcConfig {
+    pub url: Vec<Url>,
+    pub ipc: Option<PathBuf>,
+    pub quorum: Option<usize>,
+    pub parallel: Option<usize>,
+    pub report: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// Load the config from a TOML file.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Parse the hardfork overrides.
+    pub fn override_forks(&self) -> anyhow::Result<Vec<ForkOverride>> {
+        self.override_forks.iter().map(|s| s.parse()).collect()
+    }
+}
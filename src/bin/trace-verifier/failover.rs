@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use ethers_providers::{JsonRpcClient, ProviderError, RpcError};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+
+/// JSON-RPC client that sends each request to the first provider,
+/// falling back to the next ones on transport errors.
+#[derive(Debug, Clone)]
+pub struct FailoverProvider<T> {
+    providers: Vec<T>,
+}
+
+impl<T> FailoverProvider<T> {
+    /// Create a failover client, `providers` are tried in order and must not be empty.
+    pub fn new(providers: Vec<T>) -> Self {
+        assert!(!providers.is_empty(), "at least one provider is required");
+        Self { providers }
+    }
+}
+
+#[async_trait]
+impl<C: JsonRpcClient> JsonRpcClient for FailoverProvider<C> {
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let mut last_error = None;
+        for (idx, provider) in self.providers.iter().enumerate() {
+            match provider.request(method, &params).await {
+                Ok(result) => return Ok(result),
+                // the provider answered, another one won't do better
+                Err(e) if e.is_error_response() || e.is_serde_error() => return Err(e.into()),
+                Err(e) => {
+                    warn!("provider#{idx} failed on {method}: {e}, trying next provider");
+                    last_error = Some(e.into());
+                }
+            }
+        }
+        Err(last_error.unwrap())
+    }
+}
@@ -3,10 +3,21 @@
 #[macro_use]
 extern crate log;
 
-use clap::Parser;
-use stateless_block_verifier::HardforkConfig;
+use clap::{Parser, ValueEnum};
+use stateless_block_verifier::{ChainRegistry, ResourceLimits};
+use std::{io::Write, path::PathBuf};
 
+#[cfg(feature = "track-memory")]
+#[global_allocator]
+static ALLOCATOR: stateless_block_verifier::TrackingAllocator =
+    stateless_block_verifier::TrackingAllocator::new();
+
+mod chain_spec;
 mod commands;
+mod config;
+mod failover;
+mod report;
+mod transport;
 mod utils;
 
 #[derive(Parser)]
@@ -14,31 +25,193 @@ mod utils;
 struct Cli {
     #[command(subcommand)]
     commands: commands::Commands,
+    /// Path to a TOML file with default options, flags on the command line take precedence
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Path to a geth-style genesis JSON file to read the hardfork config from
+    #[arg(long)]
+    chain_spec: Option<PathBuf>,
     /// Curie block number, defaults to be determined by chain id
     #[arg(short, long)]
     curie_block: Option<u64>,
+    /// Override a hardfork activation block, e.g. `curie=100`, can be repeated
+    #[arg(long = "override-fork", value_name = "NAME=BLOCK")]
+    override_forks: Vec<chain_spec::ForkOverride>,
     /// Disable additional checks
-    #[arg(short = 'k', long)]
+    #[arg(short = 'k', long, overrides_with = "no_disable_checks")]
     disable_checks: bool,
+    /// Run additional checks even if the config file disables them
+    #[arg(long, overrides_with = "disable_checks")]
+    no_disable_checks: bool,
+    /// Memory limit in bytes for a single transaction
+    #[arg(long)]
+    memory_limit: Option<u64>,
+    /// Report call depth and memory usage of transactions
+    #[arg(long, overrides_with = "no_tx_stats")]
+    tx_stats: bool,
+    /// Don't report transaction stats even if the config file enables them
+    #[arg(long, overrides_with = "tx_stats")]
+    no_tx_stats: bool,
+    /// Record the state reads of every transaction and re-check them against the trace
+    #[arg(long, overrides_with = "no_check_reads")]
+    check_reads: bool,
+    /// Don't re-check state reads even if the config file enables it
+    #[arg(long, overrides_with = "check_reads")]
+    no_check_reads: bool,
+    /// Reject block traces with more proof nodes than this
+    #[arg(long)]
+    max_proof_nodes: Option<usize>,
+    /// Reject block traces with more contract code than this, in bytes
+    #[arg(long)]
+    max_code_bytes: Option<usize>,
+    /// Reject blocks with more transactions than this
+    #[arg(long)]
+    max_txs: Option<usize>,
+    /// Reject blocks with a transaction reaching a deeper call depth than this
+    #[arg(long)]
+    max_call_depth: Option<u64>,
+    /// Directory to write the access list of each verified block to, as `<block>.json`
+    #[arg(long)]
+    access_list_dir: Option<PathBuf>,
+    /// Log output format [default: text]
+    #[arg(long, value_enum)]
+    log_format: Option<LogFormat>,
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line
+    Json,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .format_timestamp_millis()
-        .init();
-    let cmd = Cli::parse();
+    let mut cmd = Cli::parse();
+    let config = cmd
+        .config
+        .as_deref()
+        .map(config::Config::from_file)
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    match cmd
+        .log_format
+        .or(config.log_format)
+        .unwrap_or(LogFormat::Text)
+    {
+        LogFormat::Text => logger.format_timestamp_millis(),
+        LogFormat::Json => logger.format(|buf, record| {
+            let mut line = serde_json::json!({
+                "timestamp": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            if let Some((chain_id, block_number)) = utils::LogContext::current() {
+                line["chain_id"] = chain_id.into();
+                line["block_number"] = block_number.into();
+            }
+            if let Some(tx_index) = record
+                .key_values()
+                .get(log::kv::Key::from_str("tx_index"))
+                .and_then(|value| value.to_u64())
+            {
+                line["tx_index"] = tx_index.into();
+            }
+            writeln!(buf, "{line}")
+        }),
+    };
+    logger.init();
+
+    match &mut cmd.commands {
+        commands::Commands::RunFile(run_file) => run_file.merge_config(&config.run_file),
+        commands::Commands::RunRpc(run_rpc) => run_rpc.merge_config(&config.run_rpc),
+        _ => {}
+    }
+
+    let mut chain_registry = ChainRegistry::default();
+    let chain_spec_id = match cmd.chain_spec.as_deref().or(config.chain_spec.as_deref()) {
+        Some(path) => {
+            let spec = chain_spec::ChainSpec::from_genesis_file(path)?;
+            info!(
+                "Loaded chain spec for chain id {} from {:?}",
+                spec.chain_id, path
+            );
+            chain_registry.register(spec.chain_id, spec.fork_config);
+            Some(spec.chain_id)
+        }
+        None => None,
+    };
 
+    let curie_block = cmd.curie_block.or(config.curie_block);
+    let override_forks = config
+        .override_forks()?
+        .into_iter()
+        .chain(cmd.override_forks.iter().copied())
+        .collect::<Vec<_>>();
     let get_fork_config = |chain_id: u64| {
-        let mut config = HardforkConfig::default_from_chain_id(chain_id);
-        if let Some(curie_block) = cmd.curie_block {
+        if let Some(spec_chain_id) = chain_spec_id.filter(|id| *id != chain_id) {
+            warn!(
+                "Chain id {} does not match chain spec chain id {}, using its default hardforks",
+                chain_id, spec_chain_id
+            );
+        }
+        let mut config = chain_registry.fork_config(chain_id);
+        if let Some(curie_block) = curie_block {
             config.set_curie_block(curie_block);
         }
+        for fork_override in override_forks.iter() {
+            fork_override.apply(&mut config);
+        }
         config
     };
 
-    cmd.commands
-        .run(get_fork_config, cmd.disable_checks)
-        .await?;
+    let access_list_dir = cmd
+        .access_list_dir
+        .take()
+        .or(config.access_list_dir.clone());
+    if let Some(dir) = access_list_dir.as_deref() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let options = utils::VerifyOptions {
+        disable_checks: flag(cmd.disable_checks, cmd.no_disable_checks)
+            .unwrap_or(config.disable_checks),
+        memory_limit: cmd.memory_limit.or(config.memory_limit),
+        tx_stats: flag(cmd.tx_stats, cmd.no_tx_stats).unwrap_or(config.tx_stats),
+        check_reads: flag(cmd.check_reads, cmd.no_check_reads).unwrap_or(config.check_reads),
+        limits: ResourceLimits {
+            max_proof_nodes: cmd
+                .max_proof_nodes
+                .or(config.max_proof_nodes)
+                .unwrap_or(usize::MAX),
+            max_code_bytes: cmd
+                .max_code_bytes
+                .or(config.max_code_bytes)
+                .unwrap_or(usize::MAX),
+            max_txs: cmd.max_txs.or(config.max_txs).unwrap_or(usize::MAX),
+            max_call_depth: cmd
+                .max_call_depth
+                .or(config.max_call_depth)
+                .unwrap_or(u64::MAX),
+        },
+        access_list_dir,
+    };
+
+    cmd.commands.run(get_fork_config, options).await?;
     Ok(())
 }
+
+/// Resolve a boolean flag and its `--no-` counterpart, `None` if neither is given.
+fn flag(enabled: bool, disabled: bool) -> Option<bool> {
+    match (enabled, disabled) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
@@ -0,0 +1,41 @@
+use crate::utils::VerifyReport;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Writes verification reports as they are produced,
+/// as CSV if the path ends with `.csv`, as JSON lines otherwise.
+#[derive(Debug)]
+pub enum ReportWriter {
+    Json(BufWriter<File>),
+    Csv(csv::Writer<File>),
+}
+
+impl ReportWriter {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)?;
+        if path.extension().is_some_and(|ext| ext == "csv") {
+            Ok(ReportWriter::Csv(csv::Writer::from_writer(file)))
+        } else {
+            Ok(ReportWriter::Json(BufWriter::new(file)))
+        }
+    }
+
+    /// Write a report and flush it, so the output is complete even if the process exits.
+    pub fn write(&mut self, report: &VerifyReport) -> anyhow::Result<()> {
+        match self {
+            ReportWriter::Json(writer) => {
+                serde_json::to_writer(&mut *writer, report)?;
+                writer.write_all(b"\n")?;
+                writer.flush()?;
+            }
+            ReportWriter::Csv(writer) => {
+                writer.serialize(report)?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+use ethers_providers::{Http, Ipc, JsonRpcClient, ProviderError, Ws};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt::Debug, path::Path};
+use url::Url;
+
+/// Times a websocket connection is re-established before giving up.
+const WS_RECONNECTS: usize = 10;
+
+/// JSON-RPC transport selected by the endpoint.
+#[derive(Debug, Clone)]
+pub enum Transport {
+    Http(Http),
+    Ws(Ws),
+    Ipc(Ipc),
+}
+
+impl Transport {
+    /// Connect to an `http(s)://` or `ws(s)://` URL.
+    pub async fn connect(url: &Url) -> anyhow::Result<Self> {
+        match url.scheme() {
+            "http" | "https" => Ok(Transport::Http(Http::new(url.clone()))),
+            "ws" | "wss" => Ok(Transport::Ws(
+                Ws::connect_with_reconnects(url.as_str(), WS_RECONNECTS).await?,
+            )),
+            scheme => anyhow::bail!("unsupported RPC URL scheme {scheme:?}"),
+        }
+    }
+
+    /// Connect to an IPC socket.
+    pub async fn connect_ipc(path: &Path) -> anyhow::Result<Self> {
+        Ok(Transport::Ipc(Ipc::connect(path).await?))
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for Transport {
+    type Error = ProviderError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        match self {
+            Transport::Http(client) => client.request(method, params).await.map_err(Into::into),
+            Transport::Ws(client) => client.request(method, params).await.map_err(Into::into),
+            Transport::Ipc(client) => client.request(method, params).await.map_err(Into::into),
+        }
+    }
+}
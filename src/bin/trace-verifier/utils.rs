@@ -1,19 +1,188 @@
 use eth_types::l2_types::BlockTrace;
 use eth_types::ToWord;
-use stateless_block_verifier::{EvmExecutor, HardforkConfig};
+use revm::primitives::{Address, U256};
+use serde::Serialize;
+use stateless_block_verifier::{EvmExecutor, HardforkConfig, LimitExceeded, ResourceLimits};
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+thread_local! {
+    static LOG_CONTEXT: Cell<Option<(u64, u64)>> = const { Cell::new(None) };
+}
+
+/// Chain id and block number of the block being verified on the current thread,
+/// attached to every JSON log line emitted while it is alive.
+#[derive(Debug)]
+pub struct LogContext(());
+
+impl LogContext {
+    /// Enter the context of a block on the current thread.
+    pub fn enter(chain_id: u64, block_number: u64) -> Self {
+        LOG_CONTEXT.with(|ctx| ctx.set(Some((chain_id, block_number))));
+        Self(())
+    }
+
+    /// The `(chain_id, block_number)` of the block verified on the current thread.
+    pub fn current() -> Option<(u64, u64)> {
+        LOG_CONTEXT.with(|ctx| ctx.get())
+    }
+}
+
+impl Drop for LogContext {
+    fn drop(&mut self) {
+        LOG_CONTEXT.with(|ctx| ctx.set(None));
+    }
+}
+
+/// Options applied to every verification.
+#[derive(Debug, Default, Clone)]
+pub struct VerifyOptions {
+    /// Disable additional checks
+    pub disable_checks: bool,
+    /// Memory limit in bytes for a single transaction
+    pub memory_limit: Option<u64>,
+    /// Report call depth and memory usage of transactions
+    pub tx_stats: bool,
+    /// Re-check the state reads of every transaction against the trace
+    pub check_reads: bool,
+    /// Limits on the size of block traces
+    pub limits: ResourceLimits,
+    /// Directory to write the access list of each block to
+    pub access_list_dir: Option<PathBuf>,
+}
+
+/// Access list entry, in the JSON-RPC format.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessListItem {
+    address: String,
+    storage_keys: Vec<String>,
+}
+
+/// Read a block trace file, either a bare trace or a JSON-RPC response.
+pub async fn read_trace(path: &Path) -> anyhow::Result<BlockTrace> {
+    info!("Reading trace from {:?}", path);
+    let trace = tokio::fs::read_to_string(path).await?;
+    serde_json::from_str(&trace).or_else(|_| {
+        #[derive(serde::Deserialize, Default, Debug, Clone)]
+        pub struct BlockTraceJsonRpcResult {
+            pub result: BlockTrace,
+        }
+        Ok(serde_json::from_str::<BlockTraceJsonRpcResult>(&trace)?.result)
+    })
+}
+
+/// Read and verify a trace file, reporting a trace that can't be read
+/// or whose verification panics as failed instead of returning an error.
+pub async fn verify_file(
+    path: &Path,
+    fork_config: impl Fn(u64) -> HardforkConfig,
+    options: &VerifyOptions,
+) -> VerifyReport {
+    let l2_trace = match read_trace(path).await {
+        Ok(l2_trace) => l2_trace,
+        Err(e) => {
+            error!("Failed to read trace from {:?}: {e}", path);
+            return VerifyReport::unreadable(path, e);
+        }
+    };
+    let fork_config = fork_config(l2_trace.chain_id);
+    let options = options.clone();
+    let panicked = VerifyReport::failed(&l2_trace, String::new(), Duration::ZERO);
+    let now = std::time::Instant::now();
+    tokio::task::spawn_blocking(move || verify(l2_trace, &fork_config, &options))
+        .await
+        .unwrap_or_else(|e| {
+            error!("Verification of {:?} panicked: {e}", path);
+            VerifyReport {
+                error: Some(format!("verification panicked: {e}")),
+                elapsed_ms: now.elapsed().as_millis() as u64,
+                ..panicked
+            }
+        })
+}
+
+/// Result of verifying a block.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct VerifyReport {
+    /// Chain id of the block, `None` if the trace could not be read
+    pub chain_id: Option<u64>,
+    /// Number of the block, `None` if the trace could not be read
+    pub block_number: Option<u64>,
+    pub success: bool,
+    /// Error details if the verification failed
+    pub error: Option<String>,
+    pub tx_count: usize,
+    pub gas_used: u64,
+    pub elapsed_ms: u64,
+    /// State root after the block in the trace
+    pub expected_root: String,
+    /// State root after the block computed by revm, if the block was executed
+    pub computed_root: Option<String>,
+    /// Number of accounts accessed by the block, if executed
+    pub accessed_accounts: Option<usize>,
+    /// Number of storage slots accessed by the block, if executed
+    pub accessed_storage_keys: Option<usize>,
+    /// Number of state reads that differ from the trace, if checked
+    pub read_mismatches: Option<usize>,
+    /// Index of the first transaction whose account post state (balance, nonce or
+    /// code hash) differs from the trace, storage differences are only found by `bisect`
+    pub first_divergent_tx: Option<usize>,
+    pub max_call_depth: Option<u64>,
+    pub max_memory: Option<usize>,
+    /// Peak heap allocated by the verifying thread in bytes, with the `track-memory` feature
+    pub peak_heap: Option<usize>,
+}
+
+impl VerifyReport {
+    /// Report of a block that failed before it was completely executed.
+    fn failed(l2_trace: &BlockTrace, error: String, elapsed: Duration) -> Self {
+        VerifyReport {
+            chain_id: Some(l2_trace.chain_id),
+            block_number: Some(l2_trace.header.number.unwrap().as_u64()),
+            success: false,
+            error: Some(error),
+            tx_count: l2_trace.transactions.len(),
+            gas_used: l2_trace.header.gas_used.as_u64(),
+            elapsed_ms: elapsed.as_millis() as u64,
+            expected_root: format!("{:#066x}", l2_trace.storage_trace.root_after.to_word()),
+            ..Default::default()
+        }
+    }
+
+    /// Report of a trace file that could not be read or parsed, only the error is set.
+    fn unreadable(path: &Path, e: anyhow::Error) -> Self {
+        VerifyReport {
+            error: Some(format!("failed to read trace from {path:?}: {e}")),
+            ..Default::default()
+        }
+    }
+
+    fn limit_exceeded(l2_trace: &BlockTrace, e: LimitExceeded, elapsed: Duration) -> Self {
+        error!("{e}");
+        Self::failed(l2_trace, e.to_string(), elapsed)
+    }
+}
 
 pub fn verify(
     l2_trace: BlockTrace,
     fork_config: &HardforkConfig,
-    disable_checks: bool,
-    log_error: bool,
-) -> bool {
+    options: &VerifyOptions,
+) -> VerifyReport {
+    let block_number = l2_trace.header.number.unwrap().as_u64();
+    let _log_context = LogContext::enter(l2_trace.chain_id, block_number);
     trace!("{:#?}", l2_trace);
     let root_after = l2_trace.storage_trace.root_after.to_word();
     info!("Root after in trace: {:x}", root_after);
 
     let now = std::time::Instant::now();
 
+    #[cfg(feature = "track-memory")]
+    crate::ALLOCATOR.reset_thread_peak();
+
     #[cfg(feature = "profiling")]
     let guard = pprof::ProfilerGuardBuilder::default()
         .frequency(1000)
@@ -21,8 +190,58 @@ pub fn verify(
         .build()
         .unwrap();
 
-    let mut executor = EvmExecutor::new(&l2_trace, &fork_config, disable_checks);
-    let revm_root_after = executor.handle_block(&l2_trace).to_word();
+    let mut executor = match EvmExecutor::new_with_limits(
+        &l2_trace,
+        fork_config,
+        options.disable_checks,
+        &options.limits,
+    ) {
+        Ok(executor) => executor,
+        Err(e) => return VerifyReport::limit_exceeded(&l2_trace, e, now.elapsed()),
+    };
+    if let Some(memory_limit) = options.memory_limit {
+        executor.set_memory_limit(memory_limit);
+    }
+    executor.set_collect_tx_stats(options.tx_stats);
+    executor.set_record_replay_log(options.check_reads);
+    let revm_root_after = match executor.try_handle_block(&l2_trace) {
+        Ok(root) => root.to_word(),
+        Err(e) => return VerifyReport::limit_exceeded(&l2_trace, e, now.elapsed()),
+    };
+
+    let access_list = executor.access_list();
+    if let Some(dir) = options.access_list_dir.as_deref() {
+        if let Err(e) = write_access_list(dir, block_number, &access_list) {
+            error!("Failed to write access list: {e}");
+        }
+    }
+
+    let read_mismatches = executor.replay_log().map(|replay_log| {
+        let mismatches = executor.check_replay_log();
+        for (idx, read) in mismatches.iter() {
+            error!("{idx}th tx read differs from the state trie: {read:?}");
+        }
+        info!(
+            "Checked {} state reads, {} mismatches",
+            replay_log.iter().map(Vec::len).sum::<usize>(),
+            mismatches.len()
+        );
+        mismatches.len()
+    });
+
+    let first_divergence = executor
+        .first_divergence()
+        .map(|(idx, accounts)| (idx, accounts.to_vec()));
+    let tx_stats = executor.tx_stats();
+    let max_call_depth = tx_stats.and_then(|stats| stats.iter().map(|s| s.max_call_depth).max());
+    let max_memory = tx_stats.and_then(|stats| stats.iter().map(|s| s.max_memory).max());
+    if tx_stats.is_some() {
+        info!(
+            "Max call depth: {}, max memory: {} bytes",
+            max_call_depth.unwrap_or_default(),
+            max_memory.unwrap_or_default()
+        );
+    }
 
     #[cfg(feature = "profiling")]
     if let Ok(report) = guard.report().build() {
@@ -30,25 +249,72 @@ pub fn verify(
             .join(env!("CARGO_PKG_NAME"))
             .join("profiling");
         std::fs::create_dir_all(&dir).unwrap();
-        let path = dir.join(format!(
-            "block-{}.svg",
-            l2_trace.header.number.unwrap().as_u64()
-        ));
+        let path = dir.join(format!("block-{}.svg", block_number));
         let file = std::fs::File::create(&path).unwrap();
         report.flamegraph(file).unwrap();
         info!("Profiling report saved to: {:?}", path);
     }
 
+    #[cfg(feature = "track-memory")]
+    let peak_heap = Some(crate::ALLOCATOR.thread_peak());
+    #[cfg(not(feature = "track-memory"))]
+    let peak_heap = None;
+    if let Some(peak_heap) = peak_heap {
+        info!("Peak heap usage: {} bytes", peak_heap);
+    }
+
     info!("Root after in revm: {:x}", revm_root_after);
     let elapsed = now.elapsed();
 
-    if root_after != revm_root_after {
+    let success = root_after == revm_root_after && executor.system_contracts_match();
+    let error = if success {
+        info!("Root matches in: {} ms", elapsed.as_millis());
+        None
+    } else if !executor.system_contracts_match() {
+        Some("hardfork config does not match system contracts state".to_string())
+    } else if let Some((idx, accounts)) = first_divergence.as_ref() {
+        error!("Root mismatch, first divergent tx #{idx}, accounts: {accounts:?}");
+        Some(format!(
+            "root mismatch, first divergent tx #{idx}, accounts: {accounts:?}"
+        ))
+    } else {
         error!("Root mismatch");
-        if !log_error {
-            std::process::exit(1);
-        }
-        return false;
+        Some("root mismatch".to_string())
+    };
+
+    VerifyReport {
+        chain_id: Some(l2_trace.chain_id),
+        block_number: Some(block_number),
+        success,
+        error,
+        tx_count: l2_trace.transactions.len(),
+        gas_used: l2_trace.header.gas_used.as_u64(),
+        elapsed_ms: elapsed.as_millis() as u64,
+        expected_root: format!("{:#066x}", root_after),
+        computed_root: Some(format!("{:#066x}", revm_root_after)),
+        accessed_accounts: Some(access_list.len()),
+        accessed_storage_keys: Some(access_list.iter().map(|(_, keys)| keys.len()).sum()),
+        read_mismatches,
+        first_divergent_tx: first_divergence.map(|(idx, _)| idx),
+        max_call_depth,
+        max_memory,
+        peak_heap,
     }
-    info!("Root matches in: {} ms", elapsed.as_millis());
-    true
+}
+
+fn write_access_list(
+    dir: &Path,
+    block_number: u64,
+    access_list: &[(Address, Vec<U256>)],
+) -> anyhow::Result<()> {
+    let access_list = access_list
+        .iter()
+        .map(|(address, keys)| AccessListItem {
+            address: address.to_string(),
+            storage_keys: keys.iter().map(|key| format!("{:#066x}", key)).collect(),
+        })
+        .collect::<Vec<_>>();
+    let path = dir.join(format!("{block_number}.json"));
+    std::fs::write(path, serde_json::to_vec_pretty(&access_list)?)?;
+    Ok(())
 }
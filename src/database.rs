@@ -2,32 +2,80 @@ use crate::utils::{collect_account_proofs, collect_storage_proofs};
 use eth_types::{
     l2_types::{trace::collect_codes, BlockTrace},
     state_db::{self, CodeDB, StateDB},
-    ToWord, H160,
+    ToWord, Word, H160,
 };
 use mpt_zktrie::state::ZktrieState;
 use revm::{
     db::DatabaseRef,
     primitives::{AccountInfo, Address, Bytecode, B256, U256},
 };
-use std::{convert::Infallible, fmt::Debug};
+use std::{collections::BTreeMap, convert::Infallible, fmt::Debug, sync::Mutex};
+
+/// A state read served by [`ReadOnlyDB`], with the value returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateRead {
+    /// Basic account information.
+    Account {
+        /// Account address.
+        address: Address,
+        /// Whether the account exists, other fields are zero if not.
+        exists: bool,
+        /// Account nonce.
+        nonce: u64,
+        /// Account balance.
+        balance: U256,
+        /// Poseidon code hash.
+        code_hash: B256,
+    },
+    /// Storage slot value.
+    Storage {
+        /// Account address.
+        address: Address,
+        /// Storage slot index.
+        index: U256,
+        /// Storage slot value.
+        value: U256,
+    },
+}
+
+impl StateRead {
+    fn account(address: Address, info: Option<&AccountInfo>) -> Self {
+        StateRead::Account {
+            address,
+            exists: info.is_some(),
+            nonce: info.map(|info| info.nonce).unwrap_or_default(),
+            balance: info.map(|info| info.balance).unwrap_or_default(),
+            code_hash: info.map(|info| info.code_hash).unwrap_or_default(),
+        }
+    }
+}
 
 /// EVM database that stores account and storage information.
 #[derive(Debug)]
 pub struct ReadOnlyDB {
     code_db: CodeDB,
     pub(crate) sdb: StateDB,
+    /// Storage keys covered by the trace proofs, grouped by account.
+    witness_keys: BTreeMap<H160, Vec<Word>>,
+    /// Whether reads are recorded, checked before locking `read_log`
+    /// so reads without recording never take the lock.
+    record_reads: bool,
+    /// Reads served since the last [`ReadOnlyDB::take_reads`].
+    read_log: Mutex<Vec<StateRead>>,
 }
 
 impl ReadOnlyDB {
     /// Initialize an EVM database from a block trace.
     pub fn new(l2_trace: &BlockTrace) -> Self {
         let mut sdb = StateDB::new();
+        let mut witness_keys = BTreeMap::<H160, Vec<Word>>::new();
         for parsed in
             ZktrieState::parse_account_from_proofs(collect_account_proofs(&l2_trace.storage_trace))
         {
             let (addr, acc) = parsed.unwrap();
             trace!("insert account {:?} {:?}", addr, acc);
             sdb.set_account(&addr, state_db::Account::from(&acc));
+            witness_keys.entry(addr).or_default();
         }
 
         for parsed in
@@ -36,6 +84,7 @@ impl ReadOnlyDB {
             let ((addr, key), val) = parsed.unwrap();
             let key = key.to_word();
             *sdb.get_storage_mut(&addr, &key).1 = val.into();
+            witness_keys.entry(addr).or_default().push(key);
         }
 
         let mut code_db = CodeDB::new();
@@ -43,7 +92,81 @@ impl ReadOnlyDB {
             code_db.insert_with_hash(hash, code);
         }
 
-        ReadOnlyDB { code_db, sdb }
+        ReadOnlyDB {
+            code_db,
+            sdb,
+            witness_keys,
+            record_reads: false,
+            read_log: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Record every account and storage read served, see [`ReadOnlyDB::take_reads`].
+    pub fn set_record_reads(&mut self, enabled: bool) -> &mut Self {
+        self.record_reads = enabled;
+        self.read_log.get_mut().unwrap().clear();
+        self
+    }
+
+    /// Take the reads recorded since the last call.
+    pub fn take_reads(&self) -> Vec<StateRead> {
+        if !self.record_reads {
+            return Vec::new();
+        }
+        std::mem::take(&mut *self.read_log.lock().unwrap())
+    }
+
+    fn load_account(&self, address: Address) -> Option<AccountInfo> {
+        let (exist, acc) = self.sdb.get_account(&H160::from(**address));
+        trace!("loaded account: {address:?}, exist: {exist}, acc: {acc:?}");
+        exist.then(|| AccountInfo {
+            balance: U256::from_limbs(acc.balance.0),
+            nonce: acc.nonce.as_u64(),
+            code_size: acc.code_size.as_usize(),
+            code_hash: B256::from(acc.code_hash.to_fixed_bytes()),
+            keccak_code_hash: B256::from(acc.keccak_code_hash.to_fixed_bytes()),
+            // if None, means CodeDB did not include the code, could cause by: EXTCODESIZE
+            code: self
+                .code_db
+                .0
+                .get(&acc.code_hash)
+                .map(|vec| Bytecode::new_raw(revm::primitives::Bytes::from(vec.clone()))),
+        })
+    }
+
+    fn load_storage(&self, address: Address, index: U256) -> U256 {
+        let (_, val) = self
+            .sdb
+            .get_storage(&H160::from(**address), &eth_types::U256(*index.as_limbs()));
+        U256::from_limbs(val.0)
+    }
+
+    fn record(&self, read: impl FnOnce() -> StateRead) {
+        if self.record_reads {
+            self.read_log.lock().unwrap().push(read());
+        }
+    }
+
+    /// Iterate over all existing accounts covered by the trace proofs.
+    pub fn iter_accounts(&self) -> impl Iterator<Item = (Address, AccountInfo)> + '_ {
+        self.witness_keys.keys().filter_map(|addr| {
+            let address = Address::from(addr.to_fixed_bytes());
+            let info = self.load_account(address)?;
+            Some((address, info))
+        })
+    }
+
+    /// Iterate over all storage slots of an account covered by the trace proofs.
+    pub fn iter_storage(&self, address: Address) -> impl Iterator<Item = (U256, U256)> + '_ {
+        self.witness_keys
+            .get(&H160::from(**address))
+            .into_iter()
+            .flatten()
+            .map(move |key| {
+                let index = U256::from_limbs(key.0);
+                let value = self.load_storage(address, index);
+                (index, value)
+            })
     }
 }
 
@@ -52,26 +175,9 @@ impl DatabaseRef for ReadOnlyDB {
 
     /// Get basic account information.
     fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
-        let (exist, acc) = self.sdb.get_account(&H160::from(**address));
-        trace!("loaded account: {address:?}, exist: {exist}, acc: {acc:?}");
-        if exist {
-            let acc = AccountInfo {
-                balance: U256::from_limbs(acc.balance.0),
-                nonce: acc.nonce.as_u64(),
-                code_size: acc.code_size.as_usize(),
-                code_hash: B256::from(acc.code_hash.to_fixed_bytes()),
-                keccak_code_hash: B256::from(acc.keccak_code_hash.to_fixed_bytes()),
-                // if None, means CodeDB did not include the code, could cause by: EXTCODESIZE
-                code: self
-                    .code_db
-                    .0
-                    .get(&acc.code_hash)
-                    .map(|vec| Bytecode::new_raw(revm::primitives::Bytes::from(vec.clone()))),
-            };
-            Ok(Some(acc))
-        } else {
-            Ok(None)
-        }
+        let acc = self.load_account(address);
+        self.record(|| StateRead::account(address, acc.as_ref()));
+        Ok(acc)
     }
 
     /// Get account code by its hash.
@@ -81,10 +187,13 @@ impl DatabaseRef for ReadOnlyDB {
 
     /// Get storage value of address at index.
     fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
-        let (_, val) = self
-            .sdb
-            .get_storage(&H160::from(**address), &eth_types::U256(*index.as_limbs()));
-        Ok(U256::from_limbs(val.0))
+        let value = self.load_storage(address, index);
+        self.record(|| StateRead::Storage {
+            address,
+            index,
+            value,
+        });
+        Ok(value)
     }
 
     /// Get block hash by block number.
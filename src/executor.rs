@@ -1,7 +1,10 @@
 use crate::{
     database::ReadOnlyDB,
+    inspector::{TxStats, TxStatsInspector},
+    limits::{call_depth_handle_register, CALL_DEPTH_EXCEEDED},
+    precompile::precompiles_handle_register,
     utils::{collect_account_proofs, collect_storage_proofs},
-    HardforkConfig,
+    HardforkConfig, LimitExceeded, ResourceLimits, StateRead,
 };
 use eth_types::{
     geth_types::TxType,
@@ -12,26 +15,68 @@ use log::Level;
 use mpt_zktrie::{AccountData, ZktrieState};
 use revm::{
     db::CacheDB,
-    primitives::{AccountInfo, BlockEnv, Env, SpecId, TxEnv},
+    inspector_handle_register,
+    precompile::StandardPrecompileFn,
+    primitives::{
+        AccountInfo, Address, BlockEnv, EVMError, Env, HaltReason, OutOfGasError, SpecId, TxEnv,
+    },
     DatabaseRef,
 };
-use std::fmt::Debug;
+use std::{fmt::Debug, ops::ControlFlow};
 use zktrie::ZkTrie;
 
 /// EVM executor that handles the block.
 pub struct EvmExecutor {
     db: CacheDB<ReadOnlyDB>,
     zktrie: ZkTrie,
+    /// Root of the state trie before the block
+    root_before: [u8; 32],
     spec_id: SpecId,
     disable_checks: bool,
+    system_contracts_match: bool,
+    memory_limit: Option<u64>,
+    limits: ResourceLimits,
+    tx_stats: Option<Vec<TxStats>>,
+    first_divergence: Option<(usize, Vec<Address>)>,
+    replay_log: Option<Vec<Vec<StateRead>>>,
+    precompiles: Vec<(Address, StandardPrecompileFn)>,
 }
 impl EvmExecutor {
     /// Initialize an EVM executor from a block trace as the initial state.
     pub fn new(l2_trace: &BlockTrace, fork_config: &HardforkConfig, disable_checks: bool) -> Self {
+        Self::new_with_limits(
+            l2_trace,
+            fork_config,
+            disable_checks,
+            &ResourceLimits::default(),
+        )
+        .expect("no limits set")
+    }
+
+    /// Initialize an EVM executor from a block trace as the initial state,
+    /// failing if the trace exceeds the resource limits.
+    pub fn new_with_limits(
+        l2_trace: &BlockTrace,
+        fork_config: &HardforkConfig,
+        disable_checks: bool,
+        limits: &ResourceLimits,
+    ) -> Result<Self, LimitExceeded> {
+        limits.check_trace(l2_trace)?;
+
         let block_number = l2_trace.header.number.unwrap().as_u64();
         let spec_id = fork_config.get_spec_id(block_number);
 
-        let mut db = CacheDB::new(ReadOnlyDB::new(l2_trace));
+        let mut read_only_db = ReadOnlyDB::new(l2_trace);
+        // the trace holds the state after the parent block, before any migration of this block
+        let system_contracts_match = disable_checks
+            || fork_config
+                .check_system_contracts(block_number.saturating_sub(1), &mut read_only_db)
+                .unwrap();
+        if !system_contracts_match {
+            error!("hardfork config does not match system contracts state before #{block_number}");
+        }
+
+        let mut db = CacheDB::new(read_only_db);
         fork_config
             .migrate(block_number, &mut db)
             .expect("failed to migrate");
@@ -54,23 +99,210 @@ impl EvmExecutor {
         let mem_db = zktrie_state.into_inner();
         let zktrie = mem_db.new_trie(&root).unwrap();
 
-        Self {
+        Ok(Self {
             db,
             zktrie,
+            root_before: root,
             spec_id,
             disable_checks,
+            system_contracts_match,
+            memory_limit: None,
+            limits: *limits,
+            tx_stats: None,
+            first_divergence: None,
+            replay_log: None,
+            precompiles: Vec::new(),
+        })
+    }
+
+    /// Whether the system contracts state in the trace matches the hardfork config,
+    /// always `true` if checks are disabled.
+    pub fn system_contracts_match(&self) -> bool {
+        self.system_contracts_match
+    }
+
+    /// Set the memory limit in bytes for a single transaction.
+    pub fn set_memory_limit(&mut self, memory_limit: u64) -> &mut Self {
+        self.memory_limit = Some(memory_limit);
+        self
+    }
+
+    /// Record call depth and memory usage of each transaction handled.
+    pub fn set_collect_tx_stats(&mut self, enabled: bool) -> &mut Self {
+        self.tx_stats = enabled.then(Vec::new);
+        self
+    }
+
+    /// Get the resource usage of each transaction in the last handled block,
+    /// if collection is enabled.
+    pub fn tx_stats(&self) -> Option<&[TxStats]> {
+        self.tx_stats.as_deref()
+    }
+
+    /// Add a precompile at an address, replacing the precompile of the active hardfork if any.
+    pub fn set_precompile(
+        &mut self,
+        address: Address,
+        precompile: StandardPrecompileFn,
+    ) -> &mut Self {
+        self.precompiles.retain(|(addr, _)| *addr != address);
+        self.precompiles.push((address, precompile));
+        self
+    }
+
+    /// Record the state reads of each transaction handled,
+    /// so they can be re-checked with [`EvmExecutor::check_replay_log`] afterwards.
+    pub fn set_record_replay_log(&mut self, enabled: bool) -> &mut Self {
+        self.db.db.set_record_reads(enabled);
+        self.replay_log = enabled.then(Vec::new);
+        self
+    }
+
+    /// Get the state reads served from the trace by each transaction in the last handled block,
+    /// if recording is enabled.
+    ///
+    /// Values already cached by an earlier transaction are not read again.
+    pub fn replay_log(&self) -> Option<&[Vec<StateRead>]> {
+        self.replay_log.as_deref()
+    }
+
+    /// Check the recorded state reads against the state trie before the block,
+    /// which is built from the proofs and verified against the trace root,
+    /// returning the reads that differ with the index of their transaction.
+    pub fn check_replay_log(&self) -> Vec<(usize, StateRead)> {
+        let Some(replay_log) = self.replay_log.as_ref() else {
+            return Vec::new();
+        };
+        let trie = self
+            .zktrie
+            .get_db()
+            .new_trie(&self.root_before)
+            .expect("unable to get state trie");
+        let load_account =
+            |address: Address| trie.get_account(address.as_slice()).map(AccountData::from);
+
+        let mut mismatches = Vec::new();
+        for (idx, reads) in replay_log.iter().enumerate() {
+            for read in reads.iter() {
+                let expected = match *read {
+                    StateRead::Account { address, .. } => {
+                        let account = load_account(address);
+                        StateRead::Account {
+                            address,
+                            exists: account.is_some(),
+                            nonce: account.as_ref().map(|acc| acc.nonce).unwrap_or_default(),
+                            balance: account
+                                .as_ref()
+                                .map(|acc| revm::primitives::U256::from_limbs(acc.balance.0))
+                                .unwrap_or_default(),
+                            code_hash: account
+                                .as_ref()
+                                .map(|acc| acc.poseidon_code_hash.0.into())
+                                .unwrap_or_default(),
+                        }
+                    }
+                    StateRead::Storage { address, index, .. } => {
+                        let value = load_account(address)
+                            .and_then(|acc| {
+                                let storage_trie = self
+                                    .zktrie
+                                    .get_db()
+                                    .new_trie(acc.storage_root.as_fixed_bytes())?;
+                                storage_trie.get_store(&index.to_be_bytes::<32>())
+                            })
+                            .map(revm::primitives::U256::from_be_bytes)
+                            .unwrap_or_default();
+                        StateRead::Storage {
+                            address,
+                            index,
+                            value,
+                        }
+                    }
+                };
+                if *read != expected {
+                    mismatches.push((idx, read.clone()));
+                }
+            }
         }
+        mismatches
+    }
+
+    /// Get the accounts and storage slots accessed so far, sorted by address and slot.
+    ///
+    /// Besides the accounts accessed by transactions, this includes the accounts changed by
+    /// hardfork migrations when handling the activation block of a hardfork.
+    pub fn access_list(&self) -> Vec<(Address, Vec<revm::primitives::U256>)> {
+        let mut access_list = self
+            .db
+            .accounts
+            .iter()
+            .map(|(address, account)| {
+                let mut keys = account.storage.keys().copied().collect::<Vec<_>>();
+                keys.sort();
+                (*address, keys)
+            })
+            .collect::<Vec<_>>();
+        access_list.sort_by_key(|(address, _)| *address);
+        access_list
+    }
+
+    /// Get the index of the first transaction in the last handled block whose post state
+    /// differs from the trace, along with the mismatched accounts.
+    ///
+    /// Only balances, nonces and code hashes are compared, so a transaction that
+    /// only diverges in storage is not detected here, the block then fails on the
+    /// state root alone. Always `None` if checks are disabled or the trace has no
+    /// execution results. See [`EvmExecutor::try_handle_block_with`] to inspect the
+    /// full state after each transaction.
+    pub fn first_divergence(&self) -> Option<(usize, &[Address])> {
+        self.first_divergence
+            .as_ref()
+            .map(|(idx, accounts)| (*idx, accounts.as_slice()))
     }
 
     /// Handle a block.
+    ///
+    /// Panics if a transaction exceeds the call depth or memory limit,
+    /// see [`EvmExecutor::try_handle_block`].
     pub fn handle_block(&mut self, l2_trace: &BlockTrace) -> H256 {
+        self.try_handle_block(l2_trace)
+            .expect("resource limit exceeded")
+    }
+
+    /// Handle a block, failing if a transaction exceeds the call depth limit
+    /// or is halted by the memory limit.
+    pub fn try_handle_block(&mut self, l2_trace: &BlockTrace) -> Result<H256, LimitExceeded> {
+        self.try_handle_block_with(l2_trace, |_, _| ControlFlow::Continue(()))
+    }
+
+    /// Handle a block like [`EvmExecutor::try_handle_block`], calling `after_tx` with the index
+    /// of each transaction and the state after it, before the state is committed to the trie.
+    ///
+    /// If `after_tx` breaks, the remaining transactions are skipped and the returned root
+    /// only covers the transactions executed so far.
+    pub fn try_handle_block_with(
+        &mut self,
+        l2_trace: &BlockTrace,
+        mut after_tx: impl FnMut(usize, &CacheDB<ReadOnlyDB>) -> ControlFlow<()>,
+    ) -> Result<H256, LimitExceeded> {
         debug!("handle block {:?}", l2_trace.header.number.unwrap());
         let mut env = Box::<Env>::default();
         env.cfg.chain_id = l2_trace.chain_id;
         env.block = BlockEnv::from(l2_trace);
+        if let Some(memory_limit) = self.memory_limit {
+            env.cfg.memory_limit = memory_limit;
+        }
+        if let Some(tx_stats) = self.tx_stats.as_mut() {
+            tx_stats.clear();
+        }
+        self.first_divergence = None;
+        if let Some(replay_log) = self.replay_log.as_mut() {
+            replay_log.clear();
+            self.db.db.take_reads();
+        }
 
         for (idx, tx) in l2_trace.transactions.iter().enumerate() {
-            trace!("handle {idx}th tx");
+            trace!(tx_index = idx; "handle {idx}th tx");
             trace!("{tx:#?}");
             let mut env = env.clone();
             env.tx = TxEnv::from(tx);
@@ -91,26 +323,88 @@ impl EvmExecutor {
             env.tx.scroll.is_l1_msg = tx_type.is_l1_msg();
             env.tx.scroll.rlp_bytes = Some(revm::primitives::Bytes::from(eth_tx.rlp().to_vec()));
             trace!("{env:#?}");
-            {
-                let mut revm = revm::Evm::builder()
-                    .with_db(&mut self.db)
-                    .with_spec_id(self.spec_id)
-                    .with_env(env)
-                    .build();
-                let result = revm.transact_commit().unwrap(); // TODO: handle error
-                trace!("{result:#?}");
+            let stats = self.transact_commit(idx, env)?;
+            if let Some(tx_stats) = self.tx_stats.as_mut() {
+                debug!(tx_index = idx; "{idx}th tx stats: {:?}", stats);
+                tx_stats.push(stats);
+            }
+            debug!(tx_index = idx; "handle {idx}th tx done");
+            if let Some(replay_log) = self.replay_log.as_mut() {
+                replay_log.push(self.db.db.take_reads());
             }
-            debug!("handle {idx}th tx done");
 
             if !self.disable_checks {
                 if let Some(exec) = l2_trace.execution_results.get(idx) {
-                    debug!("post check {idx}th tx");
-                    self.post_check(exec);
+                    debug!(tx_index = idx; "post check {idx}th tx");
+                    let mismatched = self.post_check(idx, exec);
+                    if !mismatched.is_empty() && self.first_divergence.is_none() {
+                        self.first_divergence = Some((idx, mismatched));
+                    }
                 }
+                // reads of the post check are not part of the execution
+                if self.replay_log.is_some() {
+                    self.db.db.take_reads();
+                }
+            }
+            let flow = after_tx(idx, &self.db);
+            if self.replay_log.is_some() {
+                self.db.db.take_reads();
+            }
+            if flow.is_break() {
+                debug!(tx_index = idx; "stop after {idx}th tx");
+                break;
             }
         }
         self.commit_changes();
-        H256::from(self.zktrie.root())
+        Ok(H256::from(self.zktrie.root()))
+    }
+
+    /// Execute a transaction and commit its changes to the database,
+    /// returning its resource usage if collection is enabled.
+    ///
+    /// Fails if the transaction exceeds the call depth limit or is halted by the memory limit.
+    /// All handler registers are applied here, so every transaction runs with the same handler.
+    fn transact_commit(&mut self, idx: usize, env: Box<Env>) -> Result<TxStats, LimitExceeded> {
+        let memory_limit = env.cfg.memory_limit;
+        let max_call_depth = self.limits.max_call_depth;
+        let mut inspector = TxStatsInspector::default();
+        let mut builder = revm::Evm::builder()
+            .with_db(&mut self.db)
+            .with_external_context(&mut inspector)
+            .with_spec_id(self.spec_id)
+            .with_env(env)
+            .append_handler_register_box(precompiles_handle_register(self.precompiles.clone()));
+        if max_call_depth != u64::MAX {
+            builder =
+                builder.append_handler_register_box(call_depth_handle_register(max_call_depth));
+        }
+        if self.tx_stats.is_some() {
+            builder = builder.append_handler_register(inspector_handle_register);
+        }
+        let mut revm = builder.build();
+        let result = revm.transact_commit();
+        drop(revm);
+        let result = match result {
+            Err(EVMError::Custom(e)) if e == CALL_DEPTH_EXCEEDED => {
+                return Err(LimitExceeded::CallDepth {
+                    tx_index: idx,
+                    limit: max_call_depth,
+                });
+            }
+            result => result.unwrap(), // TODO: handle error
+        };
+        trace!("{result:#?}");
+        if let revm::primitives::ExecutionResult::Halt {
+            reason: HaltReason::OutOfGas(OutOfGasError::MemoryLimit),
+            ..
+        } = result
+        {
+            return Err(LimitExceeded::Memory {
+                tx_index: idx,
+                limit: memory_limit,
+            });
+        }
+        Ok(inspector.stats)
     }
 
     fn commit_changes(&mut self) {
@@ -257,13 +551,15 @@ impl EvmExecutor {
         }
     }
 
-    fn post_check(&mut self, exec: &ExecutionResult) {
+    /// Check the post state of a transaction against the trace,
+    /// returning the addresses of mismatched accounts.
+    /// Storage is not compared.
+    fn post_check(&mut self, idx: usize, exec: &ExecutionResult) -> Vec<Address> {
+        let mut mismatched = Vec::new();
         for account_post_state in exec.account_after.iter() {
-            let local_acc = self
-                .db
-                .basic_ref(account_post_state.address.0.into())
-                .unwrap()
-                .unwrap();
+            let address = Address::from(account_post_state.address.0);
+            let local_acc = self.db.basic_ref(address).unwrap().unwrap();
+            let mut is_mismatch = false;
             if log_enabled!(Level::Trace) {
                 let mut local_acc = local_acc.clone();
                 local_acc.code = None;
@@ -272,7 +568,9 @@ impl EvmExecutor {
             let local_balance = U256(*local_acc.balance.as_limbs());
             if local_balance != account_post_state.balance {
                 let post = account_post_state.balance;
+                is_mismatch = true;
                 error!(
+                    tx_index = idx;
                     "incorrect balance, local {:#x} {} post {:#x} (diff {}{:#x})",
                     local_balance,
                     if local_balance < post { "<" } else { ">" },
@@ -286,25 +584,34 @@ impl EvmExecutor {
                 )
             }
             if local_acc.nonce != account_post_state.nonce {
-                error!("incorrect nonce")
+                is_mismatch = true;
+                error!(tx_index = idx; "incorrect nonce")
             }
             let p_hash = account_post_state.poseidon_code_hash;
             if p_hash.is_zero() {
                 if !local_acc.is_empty() {
-                    error!("incorrect poseidon_code_hash")
+                    is_mismatch = true;
+                    error!(tx_index = idx; "incorrect poseidon_code_hash")
                 }
             } else if local_acc.code_hash.0 != p_hash.0 {
-                error!("incorrect poseidon_code_hash")
+                is_mismatch = true;
+                error!(tx_index = idx; "incorrect poseidon_code_hash")
             }
             let k_hash = account_post_state.keccak_code_hash;
             if k_hash.is_zero() {
                 if !local_acc.is_empty() {
-                    error!("incorrect keccak_code_hash")
+                    is_mismatch = true;
+                    error!(tx_index = idx; "incorrect keccak_code_hash")
                 }
             } else if local_acc.keccak_code_hash.0 != k_hash.0 {
-                error!("incorrect keccak_code_hash")
+                is_mismatch = true;
+                error!(tx_index = idx; "incorrect keccak_code_hash")
+            }
+            if is_mismatch {
+                mismatched.push(address);
             }
         }
+        mismatched
     }
 }
 
@@ -316,3 +623,45 @@ impl Debug for EvmExecutor {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle_block_with_replay_log() -> EvmExecutor {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/mainnet_blocks/5224657.json"
+        );
+        let response: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let l2_trace: BlockTrace = serde_json::from_value(response["result"].clone()).unwrap();
+
+        let fork_config = HardforkConfig::default_from_chain_id(l2_trace.chain_id);
+        let mut executor = EvmExecutor::new(&l2_trace, &fork_config, false);
+        executor.set_record_replay_log(true);
+        executor.handle_block(&l2_trace);
+        executor
+    }
+
+    #[test]
+    fn test_replay_log_matches_pre_state() {
+        let executor = handle_block_with_replay_log();
+        let replay_log = executor.replay_log().unwrap();
+        assert_eq!(replay_log.len(), 3);
+        assert!(replay_log.iter().all(|reads| !reads.is_empty()));
+        assert!(executor.check_replay_log().is_empty());
+    }
+
+    #[test]
+    fn test_replay_log_reports_tampered_read() {
+        let mut executor = handle_block_with_replay_log();
+        let read = &mut executor.replay_log.as_mut().unwrap()[1][0];
+        match read {
+            StateRead::Account { nonce, .. } => *nonce += 1,
+            StateRead::Storage { value, .. } => *value += revm::primitives::U256::from(1),
+        }
+        let tampered = read.clone();
+        assert_eq!(executor.check_replay_log(), vec![(1, tampered)]);
+    }
+}
@@ -3,10 +3,13 @@ use eth_types::{
     l2_predeployed::l1_gas_price_oracle,
 };
 use revm::{
-    primitives::{Account, AccountStatus, Address, Bytecode, Bytes, SpecId, StorageSlot, U256},
+    precompile::{PrecompileSpecId, PrecompileWithAddress, Precompiles},
+    primitives::{
+        keccak256, Account, AccountStatus, Address, Bytecode, Bytes, SpecId, StorageSlot, U256,
+    },
     Database, DatabaseCommit,
 };
-use std::{collections::HashMap, sync::LazyLock};
+use std::{collections::HashMap, fmt, sync::LazyLock};
 
 /// Hardfork heights for Scroll networks, grouped by chain id.
 static HARDFORK_HEIGHTS: LazyLock<HashMap<u64, HashMap<SpecId, u64>>> = LazyLock::new(|| {
@@ -31,18 +34,137 @@ static HARDFORK_HEIGHTS: LazyLock<HashMap<u64, HashMap<SpecId, u64>>> = LazyLock
         .collect()
 });
 
+/// Hardfork configurations of custom Scroll-like chains, e.g. permissioned Scroll SDK chains,
+/// looked up before the built-in heights of Scroll networks.
+#[derive(Debug, Default, Clone)]
+pub struct ChainRegistry {
+    configs: HashMap<u64, HardforkConfig>,
+}
+
+impl ChainRegistry {
+    /// Register the hardfork configuration of a chain, replacing any previous one.
+    pub fn register(&mut self, chain_id: u64, config: HardforkConfig) -> &mut Self {
+        self.configs.insert(chain_id, config);
+        self
+    }
+
+    /// Check if a chain id has a registered configuration.
+    pub fn contains(&self, chain_id: u64) -> bool {
+        self.configs.contains_key(&chain_id)
+    }
+
+    /// Get the hardfork configuration of a chain id, falling back to
+    /// [`HardforkConfig::default_from_chain_id`] for unregistered chains.
+    pub fn fork_config(&self, chain_id: u64) -> HardforkConfig {
+        match self.configs.get(&chain_id) {
+            Some(config) => config.clone(),
+            None => HardforkConfig::default_from_chain_id(chain_id),
+        }
+    }
+}
+
+/// State changes of an account applied when a hardfork activates.
+#[derive(Debug, Clone)]
+pub struct StateMigration {
+    /// Address of the account to migrate.
+    pub address: Address,
+    /// New code of the account, keeps the current code if `None`.
+    pub code: Option<Bytes>,
+    /// Storage slots to overwrite.
+    pub storage: Vec<(U256, U256)>,
+}
+
+/// Error returned when a migration is added for a hardfork that is not activated after genesis.
+#[derive(Debug, Copy, Clone)]
+pub struct UnsupportedHardfork(pub SpecId);
+
+impl fmt::Display for UnsupportedHardfork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hardfork {:?} is not activated after genesis", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedHardfork {}
+
+impl StateMigration {
+    /// The upgrade of the L1 gas price oracle applied at the Curie block.
+    pub fn curie() -> Self {
+        Self {
+            address: Address::from(l1_gas_price_oracle::ADDRESS.0),
+            code: Some(Bytes::copy_from_slice(
+                l1_gas_price_oracle::V2_BYTECODE.as_slice(),
+            )),
+            storage: vec![
+                (
+                    U256::from_limbs(l1_gas_price_oracle::IS_CURIE_SLOT.0),
+                    U256::from(1),
+                ),
+                (
+                    U256::from_limbs(l1_gas_price_oracle::L1_BLOB_BASEFEE_SLOT.0),
+                    U256::from(1),
+                ),
+                (
+                    U256::from_limbs(l1_gas_price_oracle::COMMIT_SCALAR_SLOT.0),
+                    U256::from_limbs(l1_gas_price_oracle::INITIAL_COMMIT_SCALAR.0),
+                ),
+                (
+                    U256::from_limbs(l1_gas_price_oracle::BLOB_SCALAR_SLOT.0),
+                    U256::from_limbs(l1_gas_price_oracle::INITIAL_BLOB_SCALAR.0),
+                ),
+            ],
+        }
+    }
+
+    /// Apply the migration to the database.
+    pub fn apply<DB: Database + DatabaseCommit>(&self, db: &mut DB) -> Result<(), DB::Error> {
+        let mut info = db.basic(self.address)?.unwrap_or_default();
+        if let Some(code) = self.code.as_ref() {
+            info.set_code_rehash_slow(Some(Bytecode::new_raw(code.clone())));
+        }
+
+        let account = Account {
+            info,
+            storage: self
+                .storage
+                .iter()
+                .map(|(key, value)| (*key, StorageSlot::new(*value)))
+                .collect(),
+            status: AccountStatus::Touched,
+        };
+
+        db.commit(HashMap::from([(self.address, account)]));
+
+        Ok(())
+    }
+}
+
 /// Hardfork configuration for Scroll networks.
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct HardforkConfig {
     curie_block: u64,
+    /// Migrations applied at the activation block of a hardfork, built-in ones first.
+    migrations: Vec<(SpecId, StateMigration)>,
+}
+
+impl Default for HardforkConfig {
+    fn default() -> Self {
+        Self {
+            curie_block: 0,
+            migrations: vec![(SpecId::CURIE, StateMigration::curie())],
+        }
+    }
 }
 
 impl HardforkConfig {
+    /// Activation block of a hardfork that is not scheduled.
+    pub const NOT_SCHEDULED: u64 = u64::MAX;
+
     /// Get the default hardfork configuration for a chain id.
     pub fn default_from_chain_id(chain_id: u64) -> Self {
         if let Some(heights) = HARDFORK_HEIGHTS.get(&chain_id) {
             Self {
                 curie_block: heights.get(&SpecId::CURIE).copied().unwrap_or(0),
+                ..Default::default()
             }
         } else {
             warn!(
@@ -53,7 +175,27 @@ impl HardforkConfig {
         }
     }
 
-    /// Set the Curie block number.
+    /// Add a migration to be applied at the activation block of a hardfork,
+    /// after the built-in ones.
+    ///
+    /// Fails if the hardfork is active from genesis or not scheduled, since its
+    /// activation block is never verified; set the activation block first.
+    pub fn add_migration(
+        &mut self,
+        spec_id: SpecId,
+        migration: StateMigration,
+    ) -> Result<&mut Self, UnsupportedHardfork> {
+        if !self
+            .hardforks()
+            .any(|(id, height)| id == spec_id && height != 0)
+        {
+            return Err(UnsupportedHardfork(spec_id));
+        }
+        self.migrations.push((spec_id, migration));
+        Ok(self)
+    }
+
+    /// Set the Curie block number, [`HardforkConfig::NOT_SCHEDULED`] if Curie is never activated.
     pub fn set_curie_block(&mut self, curie_block: u64) -> &mut Self {
         self.curie_block = curie_block;
         self
@@ -61,64 +203,163 @@ impl HardforkConfig {
 
     /// Get the hardfork spec id for a block number.
     pub fn get_spec_id(&self, block_number: u64) -> SpecId {
-        if block_number < self.curie_block {
+        if self.curie_block == Self::NOT_SCHEDULED || block_number < self.curie_block {
             SpecId::BERNOULLI
         } else {
             SpecId::CURIE
         }
     }
 
+    /// Get the precompiles active at a block number, in ascending order of address.
+    ///
+    /// Each entry is the implementation used for the spec of the block,
+    /// calling it gives the gas cost and input limits in effect.
+    pub fn active_precompiles_at(&self, block_number: u64) -> Vec<PrecompileWithAddress> {
+        let spec_id = PrecompileSpecId::from_spec_id(self.get_spec_id(block_number));
+        let precompiles = Precompiles::new(spec_id);
+        let mut addresses = precompiles.addresses().copied().collect::<Vec<_>>();
+        addresses.sort();
+        addresses
+            .into_iter()
+            .map(|address| {
+                PrecompileWithAddress(address, precompiles.get(&address).unwrap().clone())
+            })
+            .collect()
+    }
+
+    /// Get the hardforks active at a block number, in activation order.
+    pub fn active_hardforks_at(&self, block_number: u64) -> Vec<SpecId> {
+        self.hardforks()
+            .filter(|(_, height)| *height <= block_number)
+            .map(|(spec_id, _)| spec_id)
+            .collect()
+    }
+
+    /// Get the first hardfork activated after a block number and its activation height.
+    pub fn next_fork_after(&self, block_number: u64) -> Option<(SpecId, u64)> {
+        self.hardforks().find(|(_, height)| *height > block_number)
+    }
+
+    /// Scheduled hardforks with their activation heights, in activation order.
+    fn hardforks(&self) -> impl Iterator<Item = (SpecId, u64)> {
+        [(SpecId::BERNOULLI, 0), (SpecId::CURIE, self.curie_block)]
+            .into_iter()
+            .filter(|(_, height)| *height != Self::NOT_SCHEDULED)
+    }
+
     /// Migrate the database to a new hardfork.
     pub fn migrate<DB: Database + DatabaseCommit>(
         &self,
         block_number: u64,
         db: &mut DB,
     ) -> Result<(), DB::Error> {
-        if block_number == self.curie_block {
-            info!("Apply curie migrate at height #{}", block_number);
-            self.curie_migrate(db)?;
-        };
+        for (spec_id, height) in self.hardforks() {
+            if block_number != height {
+                continue;
+            }
+            let migrations = self
+                .migrations
+                .iter()
+                .filter(|(id, _)| *id == spec_id)
+                .map(|(_, migration)| migration);
+            for migration in migrations {
+                info!(
+                    "Apply {:?} migration of {} at height #{}",
+                    spec_id, migration.address, block_number
+                );
+                migration.apply(db)?;
+            }
+        }
         Ok(())
     }
 
-    fn curie_migrate<DB: Database + DatabaseCommit>(&self, db: &mut DB) -> Result<(), DB::Error> {
+    /// Check the configuration against the system contracts state after a block number,
+    /// `db` must not have the migrations of later blocks applied.
+    ///
+    /// Returns `false` if the on-chain state disagrees with the configured hardforks,
+    /// e.g. the L1 gas price oracle is upgraded but Curie is not active yet.
+    /// System contracts missing from the database are not checked.
+    pub fn check_system_contracts<DB: Database>(
+        &self,
+        block_number: u64,
+        db: &mut DB,
+    ) -> Result<bool, DB::Error> {
         let l1_gas_price_oracle_addr = Address::from(l1_gas_price_oracle::ADDRESS.0);
-        let mut l1_gas_price_oracle_info = db.basic(l1_gas_price_oracle_addr)?.unwrap_or_default();
-        // Set the new code
-        l1_gas_price_oracle_info.set_code_rehash_slow(Some(Bytecode::new_raw(
-            Bytes::copy_from_slice(l1_gas_price_oracle::V2_BYTECODE.as_slice()),
-        )));
-
-        let l1_gas_price_oracle_acc = Account {
-            info: l1_gas_price_oracle_info,
-            storage: HashMap::from([
-                (
-                    U256::from_limbs(l1_gas_price_oracle::IS_CURIE_SLOT.0),
-                    StorageSlot::new(U256::from(1)),
-                ),
-                (
-                    U256::from_limbs(l1_gas_price_oracle::L1_BLOB_BASEFEE_SLOT.0),
-                    StorageSlot::new(U256::from(1)),
-                ),
-                (
-                    U256::from_limbs(l1_gas_price_oracle::COMMIT_SCALAR_SLOT.0),
-                    StorageSlot::new(U256::from_limbs(
-                        l1_gas_price_oracle::INITIAL_COMMIT_SCALAR.0,
-                    )),
-                ),
-                (
-                    U256::from_limbs(l1_gas_price_oracle::BLOB_SCALAR_SLOT.0),
-                    StorageSlot::new(U256::from_limbs(l1_gas_price_oracle::INITIAL_BLOB_SCALAR.0)),
-                ),
-            ]),
-            status: AccountStatus::Touched,
+        let Some(info) = db.basic(l1_gas_price_oracle_addr)? else {
+            return Ok(true);
         };
+        let is_curie_code =
+            info.keccak_code_hash == keccak256(l1_gas_price_oracle::V2_BYTECODE.as_slice());
+        let is_curie = self.get_spec_id(block_number) == SpecId::CURIE;
+        Ok(is_curie_code == is_curie)
+    }
+}
 
-        db.commit(HashMap::from([(
-            l1_gas_price_oracle_addr,
-            l1_gas_price_oracle_acc,
-        )]));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::InMemoryDB;
 
-        Ok(())
+    fn migration(slot: u64) -> StateMigration {
+        StateMigration {
+            address: Address::with_last_byte(1),
+            code: None,
+            storage: vec![(U256::from(slot), U256::from(1))],
+        }
+    }
+
+    #[test]
+    fn test_add_migration_rejects_forks_never_crossed() {
+        let mut config = HardforkConfig::default();
+        assert!(config
+            .add_migration(SpecId::BERNOULLI, migration(0))
+            .is_err());
+        // Curie at genesis
+        assert!(config.add_migration(SpecId::CURIE, migration(0)).is_err());
+        config.set_curie_block(HardforkConfig::NOT_SCHEDULED);
+        assert!(config.add_migration(SpecId::CURIE, migration(0)).is_err());
+        config.set_curie_block(10);
+        assert!(config.add_migration(SpecId::CURIE, migration(0)).is_ok());
+    }
+
+    #[test]
+    fn test_chain_registry() {
+        let mut config = HardforkConfig::default();
+        config.set_curie_block(10);
+        let mut registry = ChainRegistry::default();
+        registry.register(1234, config);
+
+        assert!(registry.contains(1234));
+        assert_eq!(registry.fork_config(1234).get_spec_id(9), SpecId::BERNOULLI);
+        assert_eq!(registry.fork_config(1234).get_spec_id(10), SpecId::CURIE);
+        // unregistered chains get every fork from genesis
+        assert_eq!(registry.fork_config(4321).get_spec_id(0), SpecId::CURIE);
+    }
+
+    #[test]
+    fn test_migrate_at_activation_block() {
+        let mut config = HardforkConfig::default();
+        config.set_curie_block(10);
+        config.add_migration(SpecId::CURIE, migration(7)).unwrap();
+
+        let mut db = InMemoryDB::default();
+        config.migrate(9, &mut db).unwrap();
+        assert!(db.accounts.is_empty());
+
+        config.migrate(10, &mut db).unwrap();
+        let oracle = StateMigration::curie();
+        let info = db.basic(oracle.address).unwrap().unwrap();
+        assert_eq!(
+            info.keccak_code_hash,
+            keccak256(l1_gas_price_oracle::V2_BYTECODE.as_slice())
+        );
+        for (slot, value) in oracle.storage {
+            assert_eq!(db.storage(oracle.address, slot).unwrap(), value);
+        }
+        assert_eq!(
+            db.storage(Address::with_last_byte(1), U256::from(7))
+                .unwrap(),
+            U256::from(1)
+        );
     }
 }
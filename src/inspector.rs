@@ -0,0 +1,33 @@
+use revm::{interpreter::Interpreter, Database, EvmContext, Inspector};
+
+/// Resource usage observed while executing a single transaction.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct TxStats {
+    /// Deepest call depth reached.
+    pub max_call_depth: u64,
+    /// Largest memory size of the transaction, summed over the active call frames, in bytes.
+    ///
+    /// This is the size the memory limit is checked against.
+    pub max_memory: usize,
+}
+
+/// Inspector that records [`TxStats`] for a transaction.
+#[derive(Debug, Default)]
+pub(crate) struct TxStatsInspector {
+    pub(crate) stats: TxStats,
+    /// Memory size of each active call frame, indexed by depth.
+    frame_memory: Vec<usize>,
+}
+
+impl<DB: Database> Inspector<DB> for TxStatsInspector {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        let depth = context.journaled_state.depth();
+        self.stats.max_call_depth = self.stats.max_call_depth.max(depth);
+
+        // frames deeper than the current one have returned and freed their memory
+        self.frame_memory.resize(depth as usize + 1, 0);
+        self.frame_memory[depth as usize] = interp.shared_memory.len();
+        let memory = self.frame_memory.iter().sum();
+        self.stats.max_memory = self.stats.max_memory.max(memory);
+    }
+}
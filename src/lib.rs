@@ -6,11 +6,18 @@
 #[macro_use]
 extern crate log;
 
+mod allocator;
 mod database;
 mod executor;
 mod hardfork;
+mod inspector;
+mod limits;
+mod precompile;
 mod utils;
 
-pub use database::ReadOnlyDB;
+pub use allocator::TrackingAllocator;
+pub use database::{ReadOnlyDB, StateRead};
 pub use executor::EvmExecutor;
-pub use hardfork::HardforkConfig;
+pub use hardfork::{ChainRegistry, HardforkConfig, StateMigration, UnsupportedHardfork};
+pub use inspector::TxStats;
+pub use limits::{LimitExceeded, ResourceLimits};
@@ -0,0 +1,238 @@
+use crate::utils::{collect_account_proofs, collect_storage_proofs};
+use eth_types::l2_types::BlockTrace;
+use revm::{handler::register::HandleRegisterBox, primitives::EVMError, Database};
+use std::{fmt, sync::Arc};
+
+/// Message of the error aborting a transaction at the call depth limit.
+pub(crate) const CALL_DEPTH_EXCEEDED: &str = "call depth limit exceeded";
+
+/// Limits on the size of a block trace, checked before building the state from it,
+/// so a corrupted or malicious trace is rejected instead of exhausting memory.
+///
+/// All limits are disabled by default.
+#[derive(Debug, Copy, Clone)]
+pub struct ResourceLimits {
+    /// Maximum number of proof nodes in the storage trace.
+    pub max_proof_nodes: usize,
+    /// Maximum total size of contract code in bytes.
+    pub max_code_bytes: usize,
+    /// Maximum number of transactions in the block.
+    pub max_txs: usize,
+    /// Maximum call depth of a transaction, the transaction is aborted
+    /// as soon as a call or create would go deeper.
+    pub max_call_depth: u64,
+}
+
+/// Error returned when a block exceeds a [`ResourceLimits`] limit or the memory limit.
+#[derive(Debug, Copy, Clone)]
+pub enum LimitExceeded {
+    /// The block trace is larger than a limit.
+    Trace {
+        /// Name of the exceeded resource.
+        resource: &'static str,
+        /// Configured limit.
+        limit: usize,
+        /// Size found in the trace.
+        actual: usize,
+    },
+    /// A transaction tried to call deeper than the call depth limit.
+    CallDepth {
+        /// Index of the transaction in the block.
+        tx_index: usize,
+        /// Configured call depth limit.
+        limit: u64,
+    },
+    /// A transaction was halted by the memory limit.
+    Memory {
+        /// Index of the transaction in the block.
+        tx_index: usize,
+        /// Configured memory limit in bytes.
+        limit: u64,
+    },
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            max_proof_nodes: usize::MAX,
+            max_code_bytes: usize::MAX,
+            max_txs: usize::MAX,
+            max_call_depth: u64::MAX,
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// Check the number of transactions, proof nodes and code bytes of a block trace.
+    pub fn check_trace(&self, l2_trace: &BlockTrace) -> Result<(), LimitExceeded> {
+        LimitExceeded::check("transactions", self.max_txs, l2_trace.transactions.len())?;
+
+        let code_bytes = l2_trace.codes.iter().map(|code| code.code.len()).sum();
+        LimitExceeded::check("code bytes", self.max_code_bytes, code_bytes)?;
+
+        let storage_trace = &l2_trace.storage_trace;
+        let proof_nodes = collect_account_proofs(storage_trace)
+            .map(|(_, proof)| proof.into_iter().count())
+            .sum::<usize>()
+            + collect_storage_proofs(storage_trace)
+                .map(|(_, _, proof)| proof.into_iter().count())
+                .sum::<usize>()
+            + storage_trace.deletion_proofs.len();
+        LimitExceeded::check("proof nodes", self.max_proof_nodes, proof_nodes)
+    }
+}
+
+impl LimitExceeded {
+    fn check(resource: &'static str, limit: usize, actual: usize) -> Result<(), Self> {
+        if actual > limit {
+            return Err(Self::Trace {
+                resource,
+                limit,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Trace {
+                resource,
+                limit,
+                actual,
+            } => write!(
+                f,
+                "block trace has {actual} {resource}, exceeding the limit of {limit}"
+            ),
+            Self::CallDepth { tx_index, limit } => write!(
+                f,
+                "transaction #{tx_index} exceeded the call depth limit of {limit}"
+            ),
+            Self::Memory { tx_index, limit } => write!(
+                f,
+                "transaction #{tx_index} exceeded the memory limit of {limit} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Handler register that aborts a transaction with [`CALL_DEPTH_EXCEEDED`]
+/// before a call or create frame deeper than `max_call_depth` is created.
+pub(crate) fn call_depth_handle_register<EXT, DB: Database>(
+    max_call_depth: u64,
+) -> HandleRegisterBox<EXT, DB> {
+    Box::new(move |handler| {
+        let call = handler.execution.call.clone();
+        handler.execution.call = Arc::new(move |context, inputs| {
+            check_call_depth(context.evm.journaled_state.depth(), max_call_depth)?;
+            call(context, inputs)
+        });
+        let create = handler.execution.create.clone();
+        handler.execution.create = Arc::new(move |context, inputs| {
+            check_call_depth(context.evm.journaled_state.depth(), max_call_depth)?;
+            create(context, inputs)
+        });
+    })
+}
+
+/// Check that a frame created at `depth` stays within `max_call_depth`,
+/// the new frame is one level deeper than the current one.
+fn check_call_depth<E>(depth: u64, max_call_depth: u64) -> Result<(), EVMError<E>> {
+    if depth >= max_call_depth {
+        return Err(EVMError::Custom(CALL_DEPTH_EXCEEDED.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eth_types::{
+        l2_types::{BytecodeTrace, TransactionTrace},
+        Bytes,
+    };
+
+    fn trace(txs: usize, code_bytes: usize, proof_nodes: usize) -> BlockTrace {
+        let mut l2_trace = BlockTrace::default();
+        l2_trace.transactions = vec![TransactionTrace::default(); txs];
+        l2_trace.codes = vec![BytecodeTrace {
+            code: vec![0; code_bytes].into(),
+        }];
+        l2_trace.storage_trace.deletion_proofs = vec![Bytes::default(); proof_nodes];
+        l2_trace
+    }
+
+    #[test]
+    fn test_default_limits_accept_any_trace() {
+        let limits = ResourceLimits::default();
+        assert!(limits.check_trace(&trace(100, 100, 100)).is_ok());
+        assert!(check_call_depth::<()>(1024, limits.max_call_depth).is_ok());
+    }
+
+    #[test]
+    fn test_check_trace_boundaries() {
+        let limits = ResourceLimits {
+            max_txs: 2,
+            max_code_bytes: 10,
+            max_proof_nodes: 3,
+            ..Default::default()
+        };
+        assert!(limits.check_trace(&trace(2, 10, 3)).is_ok());
+
+        let e = limits.check_trace(&trace(3, 10, 3)).unwrap_err();
+        assert!(matches!(
+            e,
+            LimitExceeded::Trace {
+                resource: "transactions",
+                limit: 2,
+                actual: 3
+            }
+        ));
+        let e = limits.check_trace(&trace(2, 11, 3)).unwrap_err();
+        assert!(matches!(
+            e,
+            LimitExceeded::Trace {
+                resource: "code bytes",
+                limit: 10,
+                actual: 11
+            }
+        ));
+        let e = limits.check_trace(&trace(2, 10, 4)).unwrap_err();
+        assert!(matches!(
+            e,
+            LimitExceeded::Trace {
+                resource: "proof nodes",
+                limit: 3,
+                actual: 4
+            }
+        ));
+        assert_eq!(
+            e.to_string(),
+            "block trace has 4 proof nodes, exceeding the limit of 3"
+        );
+    }
+
+    #[test]
+    fn test_check_call_depth_boundary() {
+        // the top-level call is created at depth 0 and runs at depth 1
+        assert!(check_call_depth::<()>(0, 1).is_ok());
+        assert!(check_call_depth::<()>(4, 5).is_ok());
+        assert!(matches!(
+            check_call_depth::<()>(5, 5),
+            Err(EVMError::Custom(e)) if e == CALL_DEPTH_EXCEEDED
+        ));
+
+        let e = LimitExceeded::CallDepth {
+            tx_index: 2,
+            limit: 5,
+        };
+        assert_eq!(
+            e.to_string(),
+            "transaction #2 exceeded the call depth limit of 5"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use revm::{
+    handler::register::HandleRegisterBox,
+    precompile::{Precompile, StandardPrecompileFn},
+    primitives::Address,
+    ContextPrecompile, Database,
+};
+use std::sync::Arc;
+
+/// Handler register that adds precompiles on top of the ones of the active hardfork,
+/// replacing existing precompiles at the same addresses.
+pub(crate) fn precompiles_handle_register<EXT, DB: Database>(
+    precompiles: Vec<(Address, StandardPrecompileFn)>,
+) -> HandleRegisterBox<EXT, DB> {
+    Box::new(move |handler| {
+        if precompiles.is_empty() {
+            return;
+        }
+        let precompiles = precompiles.clone();
+        let load_precompiles = handler.pre_execution.load_precompiles.clone();
+        handler.pre_execution.load_precompiles = Arc::new(move || {
+            let mut context_precompiles = load_precompiles();
+            context_precompiles.extend(precompiles.iter().map(|&(address, precompile)| {
+                (
+                    address,
+                    ContextPrecompile::Ordinary(Precompile::Standard(precompile)),
+                )
+            }));
+            context_precompiles
+        });
+    })
+}